@@ -0,0 +1,57 @@
+#!/usr/bin/env bash
+# Runs a full proposal flow against the mock canisters set up by `make deploy-mocks`:
+# link an address, vote with its balance and let the timer execute the proposal, then check
+# that a reverted transaction is recorded as a failed attempt.
+set -euo pipefail
+
+ADDRESS="0x00000000000000000000000000000000000e2e01"
+BALANCE="1_000_000_000_000_000_000"
+DURATION_SECONDS=10
+
+call() {
+    dfx canister call --network local "$@"
+}
+
+echo "Linking $(dfx identity get-principal) to $ADDRESS"
+call mock_siwe_provider set_address "(principal \"$(dfx identity get-principal)\", \"$ADDRESS\")"
+call mock_evm_rpc set_balance "(\"$ADDRESS\", $BALANCE)"
+call backend save_my_profile '("e2e", "")'
+
+PROPOSAL_ID=$(call backend submit_proposal \
+    "(\"E2E proposal\", \"Created by e2e_local.sh\", \"test\", $DURATION_SECONDS)" \
+    | sed -E 's/[^0-9]*([0-9_]+).*/\1/' | tr -d _)
+echo "Submitted proposal $PROPOSAL_ID"
+
+call backend vote_on_proposal "($PROPOSAL_ID, true)" | grep -q Ok \
+    || { echo "Vote on proposal $PROPOSAL_ID failed"; exit 1; }
+
+# Wait for the voting period to end and the timer to execute the proposal
+sleep $((DURATION_SECONDS + 15))
+
+call backend get_proposals | grep -q "eth_transaction_hash = opt" \
+    || { echo "Proposal $PROPOSAL_ID was not executed"; exit 1; }
+call mock_evm_rpc get_transactions | grep -q "block_number = opt" \
+    || { echo "No transaction was mined"; exit 1; }
+
+echo "Proposal $PROPOSAL_ID was executed and its transaction mined"
+
+# A reverted transaction counts as a failed attempt and reopens the proposal for a retry
+call mock_evm_rpc set_auto_mine '(false)'
+PROPOSAL_ID=$(call backend submit_proposal \
+    "(\"E2E revert\", \"Created by e2e_local.sh\", \"test\", $DURATION_SECONDS)" \
+    | sed -E 's/[^0-9]*([0-9_]+).*/\1/' | tr -d _)
+call backend vote_on_proposal "($PROPOSAL_ID, true)" | grep -q Ok \
+    || { echo "Vote on proposal $PROPOSAL_ID failed"; exit 1; }
+sleep $((DURATION_SECONDS + 15))
+
+TX_HASH=$(call mock_evm_rpc get_transactions | grep -oE 'hash = "0x[0-9a-f]+"' | tail -n 1 | cut -d '"' -f 2)
+call mock_evm_rpc set_receipt_status "(\"$TX_HASH\", 0)" | grep -q Ok \
+    || { echo "Transaction of proposal $PROPOSAL_ID was not sent"; exit 1; }
+call mock_evm_rpc mine_block
+call mock_evm_rpc set_auto_mine '(true)'
+sleep 15
+
+call backend get_proposals | grep -qi "Transaction $TX_HASH reverted" \
+    || { echo "Revert of $TX_HASH was not recorded"; exit 1; }
+
+echo "Revert of proposal $PROPOSAL_ID was recorded as a failed execution attempt"
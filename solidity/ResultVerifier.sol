@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.5;
+
+// Stores proposal results attested by the voting canister. Anyone can relay an attestation
+// returned by `get_result_attestation`.
+contract ResultVerifier {
+    bytes32 private constant DOMAIN_TYPEHASH =
+        keccak256("EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)");
+    bytes32 private constant RESULT_TYPEHASH =
+        keccak256("ProposalResult(uint256 proposalId,bytes32 contentHash,uint256 yesVotes,uint256 noVotes,uint256 snapshotBlock,bool passed)");
+
+    struct ProposalResult {
+        uint256 proposalId;
+        bytes32 contentHash;
+        uint256 yesVotes;
+        uint256 noVotes;
+        uint256 snapshotBlock;
+        bool passed;
+    }
+
+    // The canister's Ethereum address
+    address public immutable signer;
+    bytes32 public immutable domainSeparator;
+    mapping(uint256 => ProposalResult) public results;
+    mapping(uint256 => bool) public attested;
+
+    event ResultAttested(uint256 indexed proposalId, bytes32 contentHash, bool passed);
+
+    constructor(address _signer) {
+        signer = _signer;
+        domainSeparator = keccak256(
+            abi.encode(
+                DOMAIN_TYPEHASH,
+                keccak256("MultiChainVoting"),
+                keccak256("1"),
+                block.chainid,
+                address(this)
+            )
+        );
+    }
+
+    function submitResult(ProposalResult calldata result, bytes calldata signature) external {
+        require(!attested[result.proposalId], "Result already attested");
+        require(signature.length == 65, "Invalid signature length");
+        bytes32 structHash = keccak256(
+            abi.encode(
+                RESULT_TYPEHASH,
+                result.proposalId,
+                result.contentHash,
+                result.yesVotes,
+                result.noVotes,
+                result.snapshotBlock,
+                result.passed
+            )
+        );
+        bytes32 digest = keccak256(abi.encodePacked("\x19\x01", domainSeparator, structHash));
+        bytes32 r = bytes32(signature[0:32]);
+        bytes32 s = bytes32(signature[32:64]);
+        uint8 v = uint8(signature[64]);
+        require(ecrecover(digest, v, r, s) == signer, "Invalid signature");
+
+        attested[result.proposalId] = true;
+        results[result.proposalId] = result;
+        emit ResultAttested(result.proposalId, result.contentHash, result.passed);
+    }
+}
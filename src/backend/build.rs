@@ -21,7 +21,7 @@ fn main() {
     let mut evm_rpc = Config::new("evm_rpc");
     evm_rpc
         .binding
-        .set_type_attributes("#[derive(Debug, CandidType, Deserialize)]".into());
+        .set_type_attributes("#[derive(Debug, Clone, CandidType, Deserialize)]".into());
     builder.add(evm_rpc);
     println!("cargo:rerun-if-env-changed=CANISTER_ID_EVM_RPC");
     println!("cargo:rerun-if-env-changed=CANISTER_CANDID_PATH_EVM_RPC");
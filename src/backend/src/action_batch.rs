@@ -0,0 +1,237 @@
+use candid::{CandidType, Deserialize, Nat};
+use ethers_core::abi::{encode, Token};
+use ic_cdk::api::time;
+use ic_cdk::println;
+
+use crate::config::read_config;
+use crate::eth_rpc::{nat_to_u256, parse_address, to_hex};
+use crate::execution_payload::ExecutionPayload;
+use crate::execution_retry::stop_execution;
+use crate::pending_transaction::PendingTransaction;
+use crate::transaction::{submit_transaction, TransactionRequest, TransactionType};
+use crate::PROPOSALS;
+
+// Keeps sequential batches from occupying the canister's nonces for too many blocks
+const MAX_BATCH_ACTIONS: usize = 16;
+const AGGREGATE3_VALUE_SIGNATURE: &str = "aggregate3Value((address,bool,uint256,bytes)[])";
+
+/// How the actions of a batch are sent.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchMode {
+    /// One transaction per action, each sent once the previous one succeeded.
+    Sequential,
+    /// A single Multicall3 `aggregate3Value` transaction that reverts as a whole. The targets
+    /// see the Multicall contract as the caller, not the canister's address.
+    Multicall,
+}
+
+/// Ordered contract calls a proposal performs once it passes.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ActionBatch {
+    pub actions: Vec<ExecutionPayload>,
+    pub mode: BatchMode,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionStatus {
+    /// Not sent yet.
+    Pending,
+    /// Broadcast and waiting to be mined.
+    Sent,
+    Succeeded,
+    Reverted,
+    /// Its transaction was replaced by a cancellation.
+    Cancelled,
+    /// Not sent because an earlier action reverted or was cancelled.
+    Skipped,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ActionResult {
+    pub status: ActionStatus,
+    /// Hash of the transaction carrying the action, shared by all actions of a multicall.
+    pub tx_hash: Option<String>,
+}
+
+impl ActionBatch {
+    /// Replaces action targets given as ENS names with their addresses.
+    pub async fn resolve_ens_targets(mut self) -> Result<Self, String> {
+        let mut actions = Vec::with_capacity(self.actions.len());
+        for (i, action) in self.actions.into_iter().enumerate() {
+            let action = action
+                .resolve_ens_target()
+                .await
+                .map_err(|e| format!("Action {i}: {e}"))?;
+            actions.push(action);
+        }
+        self.actions = actions;
+        Ok(self)
+    }
+
+    /// Validates every action against the ABI of its target, see `ExecutionPayload::validate`.
+    pub fn validate(self) -> Result<Self, String> {
+        if self.actions.is_empty() {
+            return Err("A batch needs at least one action".to_string());
+        }
+        if self.actions.len() > MAX_BATCH_ACTIONS {
+            return Err(format!(
+                "A batch can have at most {MAX_BATCH_ACTIONS} actions"
+            ));
+        }
+        if self.mode == BatchMode::Multicall {
+            parse_address(&multicall_contract()?)?;
+            if self.actions.iter().any(|action| action.safe.is_some()) {
+                return Err("Multicall batches cannot route actions through the Safe".to_string());
+            }
+        }
+        let actions = self
+            .actions
+            .into_iter()
+            .enumerate()
+            .map(|(i, action)| action.validate().map_err(|e| format!("Action {i}: {e}")))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            actions,
+            mode: self.mode,
+        })
+    }
+
+    pub fn initial_results(&self) -> Vec<ActionResult> {
+        self.actions
+            .iter()
+            .map(|_| ActionResult {
+                status: ActionStatus::Pending,
+                tx_hash: None,
+            })
+            .collect()
+    }
+
+    /// Sends the first action that has not succeeded yet, or all actions at once in multicall
+    /// mode. Since every action waits for the previous one to be mined, the actions use
+    /// consecutive nonces of the canister's address.
+    pub async fn execute_next(&self, proposal_id: u64) -> Result<PendingTransaction, String> {
+        let (indices, tx) = match self.mode {
+            BatchMode::Sequential => {
+                let index = next_action(proposal_id)?;
+                let tx = self.actions[index].execute(proposal_id).await?;
+                (index..index + 1, tx)
+            }
+            BatchMode::Multicall => (
+                0..self.actions.len(),
+                submit_transaction(self.multicall_request()?, Some(proposal_id)).await?,
+            ),
+        };
+        PROPOSALS.with(|proposals| {
+            let mut proposals = proposals.borrow_mut();
+            if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+                for result in &mut proposal.action_results[indices] {
+                    result.status = ActionStatus::Sent;
+                    result.tx_hash = Some(tx.hash.clone());
+                }
+            }
+        });
+        Ok(tx)
+    }
+
+    /// The `aggregate3Value` transaction performing all actions at once.
+    pub fn multicall_request(&self) -> Result<TransactionRequest, String> {
+        let mut calls = Vec::new();
+        let mut value = Nat::from(0_u8);
+        for action in &self.actions {
+            calls.push(Token::Tuple(vec![
+                Token::Address(parse_address(&action.target)?),
+                Token::Bool(false),
+                Token::Uint(nat_to_u256(&action.value)),
+                Token::Bytes(action.calldata()?),
+            ]));
+            value += action.value.clone();
+        }
+        let selector = &ethers_core::utils::keccak256(AGGREGATE3_VALUE_SIGNATURE.as_bytes())[..4];
+        let calldata = [selector, &encode(&[Token::Array(calls)])].concat();
+        Ok(TransactionRequest {
+            to: multicall_contract()?,
+            value,
+            data: Some(to_hex(&calldata)),
+            tx_type: TransactionType::Eip1559,
+            access_list: Vec::new(),
+        })
+    }
+}
+
+fn multicall_contract() -> Result<String, String> {
+    read_config(|c| c.multicall_contract.clone())
+        .ok_or_else(|| "No Multicall contract is configured".to_string())
+}
+
+// Index of the first action that has not succeeded, which is retried after a revert
+fn next_action(proposal_id: u64) -> Result<usize, String> {
+    let results = PROPOSALS
+        .with(|proposals| {
+            proposals
+                .borrow()
+                .iter()
+                .find(|p| p.id == proposal_id)
+                .map(|p| p.action_results.clone())
+        })
+        .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
+    match results
+        .iter()
+        .position(|r| r.status != ActionStatus::Succeeded)
+    {
+        Some(index) if results[index].status == ActionStatus::Sent => Err(format!(
+            "Action {index} of proposal {proposal_id} is waiting to be mined"
+        )),
+        Some(index) => Ok(index),
+        None => Err(format!("All actions of proposal {proposal_id} succeeded")),
+    }
+}
+
+/// Records the outcome of a mined batch transaction. The proposal is executed once every action
+/// succeeded. A revert stops the batch, the remaining actions are skipped and the proposal is
+/// not retried automatically.
+pub fn record_batch_receipt(proposal_id: u64, tx_hash: &str, succeeded: bool, cancelled: bool) {
+    let stopped = PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        let proposal = proposals
+            .iter_mut()
+            .find(|p| p.id == proposal_id && p.action_batch.is_some())?;
+        let status = if cancelled {
+            ActionStatus::Cancelled
+        } else if succeeded {
+            ActionStatus::Succeeded
+        } else {
+            ActionStatus::Reverted
+        };
+        let mut first_sent = None;
+        for (i, result) in proposal.action_results.iter_mut().enumerate() {
+            if result.status == ActionStatus::Sent {
+                first_sent.get_or_insert(i);
+                result.status = status;
+                result.tx_hash = Some(tx_hash.to_string());
+            } else if status != ActionStatus::Succeeded && first_sent.is_some() {
+                result.status = ActionStatus::Skipped;
+            }
+        }
+        let index = first_sent?;
+        if status != ActionStatus::Succeeded {
+            return Some(format!(
+                "Action {index} {status:?} in transaction {tx_hash}"
+            ));
+        }
+        if proposal
+            .action_results
+            .iter()
+            .all(|r| r.status == ActionStatus::Succeeded)
+        {
+            proposal.is_executed = true;
+        } else {
+            // The timer sends the next action on its next run
+            proposal.next_execution_attempt = Some(time());
+        }
+        None
+    });
+    if let Some(error) = stopped {
+        println!("Stopped batch of proposal {}: {}", proposal_id, error);
+        stop_execution(proposal_id, error);
+    }
+}
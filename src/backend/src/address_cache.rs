@@ -0,0 +1,59 @@
+use candid::Principal;
+use ic_cdk::api::time;
+
+use crate::config::read_config;
+use crate::ADDRESS_CACHE;
+
+/// An address returned by the SIWE provider, kept until the session it was looked up for
+/// expires.
+#[derive(Clone, Debug)]
+pub struct CachedAddress {
+    pub address: String,
+    pub expires_at: u64,
+}
+
+/// The cached address of `principal`, dropping it if it has expired.
+pub fn cached_address(principal: &Principal) -> Option<String> {
+    let now = time();
+    ADDRESS_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match cache.get(principal) {
+            Some(entry) if entry.expires_at > now => Some(entry.address.clone()),
+            Some(_) => {
+                cache.remove(principal);
+                None
+            }
+            None => None,
+        }
+    })
+}
+
+/// Caches the address of `principal` until `session_expires_at`, the expiration of its
+/// delegation. Entries never outlive `siwe_session_expires_in` from now, so a caller can only
+/// shorten its own entry.
+pub fn cache_session_address(principal: Principal, address: String, session_expires_at: u64) {
+    let max_expires_at = time().saturating_add(read_config(|c| c.siwe_session_expires_in()));
+    ADDRESS_CACHE.with(|cache| {
+        cache.borrow_mut().insert(
+            principal,
+            CachedAddress {
+                address,
+                expires_at: session_expires_at.min(max_expires_at),
+            },
+        )
+    });
+}
+
+/// Replaces the cached address of `principal`, if any, keeping the expiration of its session.
+pub fn update_cached_address(principal: &Principal, address: String) {
+    ADDRESS_CACHE.with(|cache| {
+        if let Some(entry) = cache.borrow_mut().get_mut(principal) {
+            entry.address = address;
+        }
+    });
+}
+
+/// Forgets the cached address of `principal`, so that the next lookup asks the SIWE provider.
+pub fn invalidate_address(principal: &Principal) {
+    ADDRESS_CACHE.with(|cache| cache.borrow_mut().remove(principal));
+}
@@ -0,0 +1,241 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Nat};
+use ethers_core::abi::{encode, Contract, Token};
+use ethers_core::types::U256;
+use ethers_core::utils::keccak256;
+use ic_cdk::println;
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::config::read_config;
+use crate::eth_rpc::{
+    get_self_eth_address, nat_to_u256, parse_address, pubkey_and_signature, to_hex, y_parity,
+};
+use crate::{Proposal, ATTESTATIONS, PROPOSALS};
+
+/// ABI of the contract in `solidity/ResultVerifier.sol` that checks attestations.
+pub const RESULT_VERIFIER_ABI: &str = include_str!("../../../solidity/result_verifier.json");
+const DOMAIN_NAME: &str = "MultiChainVoting";
+const DOMAIN_VERSION: &str = "1";
+const DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const PROPOSAL_RESULT_TYPE: &str = "ProposalResult(uint256 proposalId,bytes32 contentHash,uint256 yesVotes,uint256 noVotes,uint256 snapshotBlock,bool passed)";
+
+/// The final result of a proposal, signed as EIP-712 typed data by the canister's root key.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ResultAttestation {
+    pub proposal_id: u64,
+    /// `keccak256(abi.encode(title, description, proposal_type))`.
+    pub content_hash: String,
+    pub yes_votes: Nat,
+    pub no_votes: Nat,
+    pub snapshot_block: Nat,
+    /// Whether the proposal passed and was not vetoed.
+    pub passed: bool,
+    pub chain_id: u64,
+    pub verifying_contract: String,
+    /// Address that produced `signature`, which the verifying contract must expect.
+    pub signer: String,
+    /// EIP-712 digest of the result.
+    pub digest: String,
+    /// 65-byte `r || s || v` signature of `digest`.
+    pub signature: String,
+    /// Calldata of `submitResult` on the verifying contract, ready to be relayed.
+    pub relay_calldata: String,
+}
+
+impl Storable for ResultAttestation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub fn attestation(proposal_id: u64) -> Option<ResultAttestation> {
+    ATTESTATIONS.with(|a| a.borrow().get(&proposal_id))
+}
+
+impl Proposal {
+    /// Whether the outcome can no longer change, which is once voting has closed and the
+    /// proposal can no longer be vetoed.
+    fn has_final_result(&self) -> bool {
+        !self.is_open && !self.in_veto_window()
+    }
+
+    /// The outcome that is attested, which is a failure for vetoed proposals.
+    fn final_result(&self) -> bool {
+        self.passed() && !self.is_vetoed
+    }
+}
+
+/// Signs the results of closed proposals that have not been attested yet, once their veto window
+/// has passed. Does nothing unless a verifying contract is configured, since it is part of the
+/// signed domain.
+pub async fn attest_closed_proposals() {
+    let Some(verifying_contract) = read_config(|c| c.attestation_verifier.clone()) else {
+        return;
+    };
+    let closed = PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .filter(|p| p.has_final_result() && attestation(p.id).is_none())
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+    for proposal in closed {
+        match attest(&proposal, &verifying_contract).await {
+            Ok(attestation) => {
+                println!("Attested the result of proposal {}", proposal.id);
+                ATTESTATIONS.with(|a| a.borrow_mut().insert(proposal.id, attestation));
+            }
+            Err(e) => println!("Error attesting proposal {}: {}", proposal.id, e),
+        }
+    }
+}
+
+async fn attest(
+    proposal: &Proposal,
+    verifying_contract: &str,
+) -> Result<ResultAttestation, String> {
+    let chain_id = read_config(|c| c.chain_id);
+    let content_hash = keccak256(encode(&[
+        Token::String(proposal.title.clone()),
+        Token::String(proposal.description.clone()),
+        Token::String(proposal.proposal_type.clone()),
+    ]));
+    let snapshot_block =
+        U256::from_str_radix(proposal.block_height.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("Invalid snapshot block {}: {e}", proposal.block_height))?;
+    let result = Token::Tuple(vec![
+        Token::Uint(proposal.id.into()),
+        Token::FixedBytes(content_hash.to_vec()),
+        Token::Uint(nat_to_u256(&proposal.yes_votes)),
+        Token::Uint(nat_to_u256(&proposal.no_votes)),
+        Token::Uint(snapshot_block),
+        Token::Bool(proposal.final_result()),
+    ]);
+    let digest = typed_data_digest(&result, chain_id, verifying_contract)?;
+
+    let (pubkey, mut signature) = pubkey_and_signature(digest.to_vec(), Vec::new()).await;
+    signature.push(27 + y_parity(&digest, &signature, &pubkey) as u8);
+
+    let verifier: Contract =
+        serde_json::from_str(RESULT_VERIFIER_ABI).map_err(|e| e.to_string())?;
+    let relay_calldata = verifier
+        .function("submitResult")
+        .and_then(|f| f.encode_input(&[result, Token::Bytes(signature.clone())]))
+        .map_err(|e| e.to_string())?;
+
+    Ok(ResultAttestation {
+        proposal_id: proposal.id,
+        content_hash: to_hex(&content_hash),
+        yes_votes: proposal.yes_votes.clone(),
+        no_votes: proposal.no_votes.clone(),
+        snapshot_block: Nat::from_str(&snapshot_block.to_string()).map_err(|e| e.to_string())?,
+        passed: proposal.final_result(),
+        chain_id,
+        verifying_contract: verifying_contract.to_string(),
+        signer: get_self_eth_address().await,
+        digest: to_hex(&digest),
+        signature: to_hex(&signature),
+        relay_calldata: to_hex(&relay_calldata),
+    })
+}
+
+/// EIP-712 digest of a `ProposalResult` given as the tuple of its fields.
+pub fn typed_data_digest(
+    result: &Token,
+    chain_id: u64,
+    verifying_contract: &str,
+) -> Result<[u8; 32], String> {
+    let domain_separator = keccak256(encode(&[
+        Token::FixedBytes(keccak256(DOMAIN_TYPE).to_vec()),
+        Token::FixedBytes(keccak256(DOMAIN_NAME).to_vec()),
+        Token::FixedBytes(keccak256(DOMAIN_VERSION).to_vec()),
+        Token::Uint(chain_id.into()),
+        Token::Address(parse_address(verifying_contract)?),
+    ]));
+    let Token::Tuple(fields) = result else {
+        return Err("A proposal result is a tuple".to_string());
+    };
+    let mut struct_fields = vec![Token::FixedBytes(keccak256(PROPOSAL_RESULT_TYPE).to_vec())];
+    struct_fields.extend(fields.iter().cloned());
+    let struct_hash = keccak256(encode(&struct_fields));
+    Ok(keccak256(
+        [&[0x19, 0x01], &domain_separator[..], &struct_hash[..]].concat(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_core::types::transaction::eip712::{Eip712, TypedData};
+
+    const VERIFIER: &str = "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC";
+
+    fn result() -> Token {
+        Token::Tuple(vec![
+            Token::Uint(7.into()),
+            Token::FixedBytes(keccak256("content").to_vec()),
+            Token::Uint(1500.into()),
+            Token::Uint(250.into()),
+            Token::Uint(6_000_000.into()),
+            Token::Bool(true),
+        ])
+    }
+
+    #[test]
+    fn digest_matches_eip712_typed_data() {
+        let typed_data: TypedData = serde_json::from_value(serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "ProposalResult": [
+                    {"name": "proposalId", "type": "uint256"},
+                    {"name": "contentHash", "type": "bytes32"},
+                    {"name": "yesVotes", "type": "uint256"},
+                    {"name": "noVotes", "type": "uint256"},
+                    {"name": "snapshotBlock", "type": "uint256"},
+                    {"name": "passed", "type": "bool"}
+                ]
+            },
+            "primaryType": "ProposalResult",
+            "domain": {
+                "name": DOMAIN_NAME,
+                "version": DOMAIN_VERSION,
+                "chainId": 11155111,
+                "verifyingContract": VERIFIER
+            },
+            "message": {
+                "proposalId": 7,
+                "contentHash": to_hex(&keccak256("content")),
+                "yesVotes": 1500,
+                "noVotes": 250,
+                "snapshotBlock": 6_000_000,
+                "passed": true
+            }
+        }))
+        .unwrap();
+
+        let digest = typed_data_digest(&result(), 11155111, VERIFIER).unwrap();
+        assert_eq!(digest, typed_data.encode_eip712().unwrap());
+        assert_eq!(to_hex(&digest), "0xf6a9af1861a3eaca04b8d7b12015ecedc038747cebf006140b84dad0e5ec8109");
+    }
+
+    #[test]
+    fn digest_depends_on_the_domain() {
+        let sepolia = typed_data_digest(&result(), 11155111, VERIFIER).unwrap();
+        let mainnet = typed_data_digest(&result(), 1, VERIFIER).unwrap();
+        assert_ne!(sepolia, mainnet);
+    }
+}
@@ -0,0 +1,88 @@
+use candid::de::IDLDeserialize;
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::call::call_raw;
+use ic_cdk::api::time;
+
+use crate::config::read_config;
+use crate::PROPOSALS;
+
+/// A canister and the methods proposals may call on it.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AllowedCanisterCall {
+    pub canister_id: Principal,
+    pub methods: Vec<String>,
+}
+
+/// An inter-canister call a proposal performs once it passes.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CanisterCall {
+    pub canister_id: Principal,
+    pub method: String,
+    /// Candid-encoded argument of the method.
+    pub arg: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum CanisterCallOutcome {
+    /// Candid-encoded reply of the method.
+    Reply(Vec<u8>),
+    Reject {
+        code: i32,
+        message: String,
+    },
+}
+
+/// Outcome of the latest attempt to perform a proposal's canister call.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CanisterCallResult {
+    pub timestamp: u64,
+    pub outcome: CanisterCallOutcome,
+}
+
+impl CanisterCall {
+    /// Checks that the call is on the allowlist and that its argument is valid Candid.
+    pub fn validate(&self) -> Result<(), String> {
+        let allowed = read_config(|c| {
+            c.canister_call_allowlist.iter().flatten().any(|entry| {
+                entry.canister_id == self.canister_id && entry.methods.contains(&self.method)
+            })
+        });
+        if !allowed {
+            return Err(format!(
+                "Method {} of canister {} is not on the allowlist",
+                self.method, self.canister_id
+            ));
+        }
+        IDLDeserialize::new(&self.arg).map_err(|e| format!("Invalid Candid argument: {e}"))?;
+        Ok(())
+    }
+
+    /// Performs the call and records its reply or reject on the proposal. The allowlist is
+    /// checked again since it may have changed while the proposal was open.
+    pub async fn execute(&self, proposal_id: u64) -> Result<Vec<u8>, String> {
+        self.validate()?;
+        let result = call_raw(self.canister_id, &self.method, &self.arg, 0).await;
+        let outcome = match &result {
+            Ok(reply) => CanisterCallOutcome::Reply(reply.clone()),
+            Err((code, message)) => CanisterCallOutcome::Reject {
+                code: *code as i32,
+                message: message.clone(),
+            },
+        };
+        PROPOSALS.with(|proposals| {
+            let mut proposals = proposals.borrow_mut();
+            if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+                proposal.canister_call_result = Some(CanisterCallResult {
+                    timestamp: time(),
+                    outcome,
+                });
+            }
+        });
+        result.map_err(|(code, message)| {
+            format!(
+                "Canister {} rejected {} with {:?}: {}",
+                self.canister_id, self.method, code, message
+            )
+        })
+    }
+}
@@ -0,0 +1,449 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Nat, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+use std::time::Duration;
+
+use crate::canister_call::AllowedCanisterCall;
+use crate::contract_registry::RegisteredContract;
+use crate::safe::SafeConfig;
+use crate::signing_space::SigningSpace;
+use crate::treasury::TreasuryToken;
+use crate::declarations::evm_rpc::{EthMainnetService, EthSepoliaService, RpcService, RpcServices};
+use crate::CONFIG;
+
+const SEPOLIA_CHAIN_ID: u64 = 11155111;
+const DEFAULT_GAS_LIMIT: u128 = 300_000;
+const DEFAULT_MAX_FEE_PER_GAS: u128 = 156_083_066_522_u128;
+const DEFAULT_MAX_PRIORITY_FEE_PER_GAS: u128 = 3_000_000_000;
+// Duration for periodic checks of proposals
+const DEFAULT_TIMER_INTERVAL_SECONDS: u64 = 60;
+// Transactions that stay unmined for longer than this are re-signed with higher fees
+const DEFAULT_STUCK_TRANSACTION_THRESHOLD_SECONDS: u64 = 10 * 60;
+// Failed executions are retried with exponential backoff
+const DEFAULT_MAX_EXECUTION_ATTEMPTS: u32 = 5;
+const DEFAULT_EXECUTION_RETRY_DELAY_SECONDS: u64 = 60;
+// Matches `session_expires_in` of the SIWE provider in the Makefile
+const DEFAULT_SIWE_SESSION_EXPIRES_IN: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GovernanceParams {
+    pub timer_interval_seconds: u64,
+    pub stuck_transaction_threshold_seconds: u64,
+    /// Number of automatic execution attempts, after which only an admin can retry.
+    pub max_execution_attempts: Option<u32>,
+    /// Delay before the first retry of a failed execution, doubled after every further failure.
+    pub execution_retry_delay_seconds: Option<u64>,
+    /// Delay between the end of voting and the execution of an accepted proposal, during which
+    /// it can be vetoed. Defaults to no delay.
+    pub timelock_seconds: Option<u64>,
+    /// Timelocks for specific proposal types, overriding `timelock_seconds`.
+    pub timelocks_by_type: Option<Vec<(String, u64)>>,
+    /// Principals that can veto a queued proposal on their own.
+    pub guardians: Option<Vec<Principal>>,
+    /// Voting power of counter-votes needed to veto a queued proposal. Counter-votes are
+    /// disabled if unset.
+    pub veto_threshold: Option<Nat>,
+    /// Voting power a submitter, or the sponsor backing them, needs at the snapshot block of a
+    /// new proposal. Anyone can submit if unset.
+    pub proposal_threshold: Option<Nat>,
+}
+
+/// Runtime configuration of the canister, persisted in stable memory.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Config {
+    pub ecdsa_key_name: String,
+    pub chain_id: u64,
+    /// Providers queried by the typed EVM RPC methods, whose responses must agree.
+    pub rpc_services: RpcServices,
+    /// Provider used for raw JSON-RPC requests such as `eth_call`.
+    pub rpc_service: RpcService,
+    pub gas_limit: Nat,
+    pub max_fee_per_gas: Nat,
+    pub max_priority_fee_per_gas: Nat,
+    pub governance: GovernanceParams,
+    /// Replaces the EVM RPC canister from `dfx.json`, e.g. with a local mock.
+    pub evm_rpc_canister: Option<Principal>,
+    /// Replaces the SIWE provider canister from `dfx.json`, e.g. with a local mock.
+    pub siwe_provider_canister: Option<Principal>,
+    /// `session_expires_in` of the SIWE provider in nanoseconds, which is the longest the address
+    /// of a principal stays cached. Defaults to one week.
+    pub siwe_session_expires_in: Option<u64>,
+    /// ERC-20 tokens the treasury holds and can transfer.
+    pub treasury_tokens: Option<Vec<TreasuryToken>>,
+    /// Address of a Multicall3 deployment, required for atomic action batches.
+    pub multicall_contract: Option<String>,
+    /// Safe that payloads can be routed through.
+    pub safe: Option<SafeConfig>,
+    /// Canister methods that proposals may call. No calls are allowed if unset.
+    pub canister_call_allowlist: Option<Vec<AllowedCanisterCall>>,
+    /// Proposal types that sign from their own derived addresses.
+    pub signing_spaces: Option<Vec<SigningSpace>>,
+    /// Deployed `ResultVerifier` contract. Results are only attested if it is set.
+    pub attestation_verifier: Option<String>,
+    /// ENS registry used to resolve names. Defaults to the registry on mainnet and Sepolia.
+    pub ens_registry: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ecdsa_key_name: "dfx_test_key".to_string(),
+            chain_id: SEPOLIA_CHAIN_ID,
+            rpc_services: RpcServices::EthSepolia(Some(vec![
+                EthSepoliaService::PublicNode,
+                EthSepoliaService::BlockPi,
+                EthSepoliaService::Ankr,
+            ])),
+            rpc_service: RpcService::EthSepolia(EthSepoliaService::BlockPi),
+            gas_limit: DEFAULT_GAS_LIMIT.into(),
+            max_fee_per_gas: DEFAULT_MAX_FEE_PER_GAS.into(),
+            max_priority_fee_per_gas: DEFAULT_MAX_PRIORITY_FEE_PER_GAS.into(),
+            governance: GovernanceParams {
+                timer_interval_seconds: DEFAULT_TIMER_INTERVAL_SECONDS,
+                stuck_transaction_threshold_seconds: DEFAULT_STUCK_TRANSACTION_THRESHOLD_SECONDS,
+                max_execution_attempts: Some(DEFAULT_MAX_EXECUTION_ATTEMPTS),
+                execution_retry_delay_seconds: Some(DEFAULT_EXECUTION_RETRY_DELAY_SECONDS),
+                timelock_seconds: None,
+                timelocks_by_type: None,
+                guardians: None,
+                veto_threshold: None,
+                proposal_threshold: None,
+            },
+            evm_rpc_canister: None,
+            siwe_provider_canister: None,
+            siwe_session_expires_in: None,
+            treasury_tokens: None,
+            multicall_contract: None,
+            safe: None,
+            canister_call_allowlist: None,
+            signing_spaces: None,
+            attestation_verifier: None,
+            ens_registry: None,
+        }
+    }
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct InitArgs {
+    pub ecdsa_key_name: String,
+    pub chain_id: Option<u64>,
+    pub rpc_services: Option<RpcServices>,
+    pub rpc_service: Option<RpcService>,
+    pub gas_limit: Option<Nat>,
+    pub max_fee_per_gas: Option<Nat>,
+    pub max_priority_fee_per_gas: Option<Nat>,
+    pub governance: Option<GovernanceParams>,
+    pub evm_rpc_canister: Option<Principal>,
+    pub siwe_provider_canister: Option<Principal>,
+    pub siwe_session_expires_in: Option<u64>,
+    pub treasury_tokens: Option<Vec<TreasuryToken>>,
+    pub multicall_contract: Option<String>,
+    pub safe: Option<SafeConfig>,
+    pub canister_call_allowlist: Option<Vec<AllowedCanisterCall>>,
+    pub signing_spaces: Option<Vec<SigningSpace>>,
+    pub attestation_verifier: Option<String>,
+    pub ens_registry: Option<String>,
+    /// Contracts registered on install, in addition to the bundled target contract.
+    pub target_contracts: Option<Vec<RegisteredContract>>,
+}
+
+/// Fields left unset keep their current value.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct UpgradeArgs {
+    pub ecdsa_key_name: Option<String>,
+    pub chain_id: Option<u64>,
+    pub rpc_services: Option<RpcServices>,
+    pub rpc_service: Option<RpcService>,
+    pub gas_limit: Option<Nat>,
+    pub max_fee_per_gas: Option<Nat>,
+    pub max_priority_fee_per_gas: Option<Nat>,
+    pub governance: Option<GovernanceParams>,
+    pub evm_rpc_canister: Option<Principal>,
+    pub siwe_provider_canister: Option<Principal>,
+    pub siwe_session_expires_in: Option<u64>,
+    pub treasury_tokens: Option<Vec<TreasuryToken>>,
+    pub multicall_contract: Option<String>,
+    pub safe: Option<SafeConfig>,
+    pub canister_call_allowlist: Option<Vec<AllowedCanisterCall>>,
+    pub signing_spaces: Option<Vec<SigningSpace>>,
+    pub attestation_verifier: Option<String>,
+    pub ens_registry: Option<String>,
+    pub target_contracts: Option<Vec<RegisteredContract>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum CanisterArg {
+    Init(InitArgs),
+    Upgrade(Option<UpgradeArgs>),
+}
+
+impl Config {
+    pub fn from_init_args(args: InitArgs) -> Self {
+        let default = Config::default();
+        Self {
+            ecdsa_key_name: args.ecdsa_key_name,
+            chain_id: args.chain_id.unwrap_or(default.chain_id),
+            rpc_services: args.rpc_services.unwrap_or(default.rpc_services),
+            rpc_service: args.rpc_service.unwrap_or(default.rpc_service),
+            gas_limit: args.gas_limit.unwrap_or(default.gas_limit),
+            max_fee_per_gas: args.max_fee_per_gas.unwrap_or(default.max_fee_per_gas),
+            max_priority_fee_per_gas: args
+                .max_priority_fee_per_gas
+                .unwrap_or(default.max_priority_fee_per_gas),
+            governance: args.governance.unwrap_or(default.governance),
+            evm_rpc_canister: args.evm_rpc_canister,
+            siwe_provider_canister: args.siwe_provider_canister,
+            siwe_session_expires_in: args.siwe_session_expires_in,
+            treasury_tokens: args.treasury_tokens,
+            multicall_contract: args.multicall_contract,
+            safe: args.safe,
+            canister_call_allowlist: args.canister_call_allowlist,
+            signing_spaces: args.signing_spaces,
+            attestation_verifier: args.attestation_verifier,
+            ens_registry: args.ens_registry,
+        }
+    }
+
+    pub fn apply_upgrade_args(&mut self, args: UpgradeArgs) {
+        if let Some(ecdsa_key_name) = args.ecdsa_key_name {
+            self.ecdsa_key_name = ecdsa_key_name;
+        }
+        if let Some(chain_id) = args.chain_id {
+            self.chain_id = chain_id;
+        }
+        if let Some(rpc_services) = args.rpc_services {
+            self.rpc_services = rpc_services;
+        }
+        if let Some(rpc_service) = args.rpc_service {
+            self.rpc_service = rpc_service;
+        }
+        if let Some(gas_limit) = args.gas_limit {
+            self.gas_limit = gas_limit;
+        }
+        if let Some(max_fee_per_gas) = args.max_fee_per_gas {
+            self.max_fee_per_gas = max_fee_per_gas;
+        }
+        if let Some(max_priority_fee_per_gas) = args.max_priority_fee_per_gas {
+            self.max_priority_fee_per_gas = max_priority_fee_per_gas;
+        }
+        if let Some(governance) = args.governance {
+            self.governance = governance;
+        }
+        if let Some(evm_rpc_canister) = args.evm_rpc_canister {
+            self.evm_rpc_canister = Some(evm_rpc_canister);
+        }
+        if let Some(siwe_provider_canister) = args.siwe_provider_canister {
+            self.siwe_provider_canister = Some(siwe_provider_canister);
+        }
+        if let Some(siwe_session_expires_in) = args.siwe_session_expires_in {
+            self.siwe_session_expires_in = Some(siwe_session_expires_in);
+        }
+        if let Some(treasury_tokens) = args.treasury_tokens {
+            self.treasury_tokens = Some(treasury_tokens);
+        }
+        if let Some(multicall_contract) = args.multicall_contract {
+            self.multicall_contract = Some(multicall_contract);
+        }
+        if let Some(safe) = args.safe {
+            self.safe = Some(safe);
+        }
+        if let Some(canister_call_allowlist) = args.canister_call_allowlist {
+            self.canister_call_allowlist = Some(canister_call_allowlist);
+        }
+        if let Some(signing_spaces) = args.signing_spaces {
+            self.signing_spaces = Some(signing_spaces);
+        }
+        if let Some(attestation_verifier) = args.attestation_verifier {
+            self.attestation_verifier = Some(attestation_verifier);
+        }
+        if let Some(ens_registry) = args.ens_registry {
+            self.ens_registry = Some(ens_registry);
+        }
+    }
+
+    /// Rejects settings the canister cannot run with, such as a zero timer interval.
+    pub fn validate(&self) -> Result<(), String> {
+        let governance = &self.governance;
+        if self.ecdsa_key_name.is_empty() {
+            return Err("ecdsa_key_name must not be empty".to_string());
+        }
+        if governance.timer_interval_seconds == 0 {
+            return Err("timer_interval_seconds must be greater than zero".to_string());
+        }
+        if governance.stuck_transaction_threshold_seconds == 0 {
+            return Err(
+                "stuck_transaction_threshold_seconds must be greater than zero".to_string(),
+            );
+        }
+        if governance.max_execution_attempts == Some(0) {
+            return Err("max_execution_attempts must be greater than zero".to_string());
+        }
+        if self.siwe_session_expires_in == Some(0) {
+            return Err("siwe_session_expires_in must be greater than zero".to_string());
+        }
+        if self.gas_limit == 0_u64 {
+            return Err("gas_limit must be greater than zero".to_string());
+        }
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err("max_priority_fee_per_gas must not exceed max_fee_per_gas".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn timer_interval(&self) -> Duration {
+        Duration::from_secs(self.governance.timer_interval_seconds)
+    }
+
+    /// Time in nanoseconds after which an unmined transaction is re-signed with higher fees.
+    pub fn stuck_transaction_threshold(&self) -> u64 {
+        self.governance
+            .stuck_transaction_threshold_seconds
+            .saturating_mul(1_000_000_000)
+    }
+
+    pub fn max_execution_attempts(&self) -> u32 {
+        self.governance
+            .max_execution_attempts
+            .unwrap_or(DEFAULT_MAX_EXECUTION_ATTEMPTS)
+    }
+
+    /// Delay in nanoseconds before retrying an execution that failed `failed_attempts` times.
+    pub fn execution_retry_delay(&self, failed_attempts: u32) -> u64 {
+        let delay_seconds = self
+            .governance
+            .execution_retry_delay_seconds
+            .unwrap_or(DEFAULT_EXECUTION_RETRY_DELAY_SECONDS);
+        let factor = 1_u64 << failed_attempts.saturating_sub(1).min(32);
+        delay_seconds
+            .saturating_mul(factor)
+            .saturating_mul(1_000_000_000)
+    }
+
+    /// Timelock in nanoseconds for proposals of `proposal_type`.
+    pub fn timelock(&self, proposal_type: &str) -> u64 {
+        let governance = &self.governance;
+        let seconds = governance
+            .timelocks_by_type
+            .iter()
+            .flatten()
+            .find(|(t, _)| t == proposal_type)
+            .map(|(_, seconds)| *seconds)
+            .or(governance.timelock_seconds)
+            .unwrap_or_default();
+        seconds.saturating_mul(1_000_000_000)
+    }
+
+    pub fn is_guardian(&self, principal: &Principal) -> bool {
+        self.governance.guardians.iter().flatten().any(|g| g == principal)
+    }
+
+    pub fn evm_rpc_canister_id(&self) -> Principal {
+        self.evm_rpc_canister
+            .unwrap_or(crate::declarations::evm_rpc::CANISTER_ID)
+    }
+
+    pub fn siwe_provider_canister_id(&self) -> Principal {
+        self.siwe_provider_canister
+            .unwrap_or(crate::declarations::ic_siwe_provider::CANISTER_ID)
+    }
+
+    pub fn siwe_session_expires_in(&self) -> u64 {
+        self.siwe_session_expires_in
+            .unwrap_or(DEFAULT_SIWE_SESSION_EXPIRES_IN)
+    }
+}
+
+pub fn read_config<R>(f: impl FnOnce(&Config) -> R) -> R {
+    CONFIG.with(|c| f(c.borrow().get()))
+}
+
+pub fn set_config(config: Config) {
+    CONFIG.with(|c| {
+        c.borrow_mut()
+            .set(config)
+            .expect("failed to write the config to stable memory")
+    });
+}
+
+/// Lists the individual providers behind `services`, which the EVM RPC canister charges for
+/// separately. Without an explicit provider list all providers of the chain are assumed.
+pub fn expand_rpc_services(services: &RpcServices) -> Vec<RpcService> {
+    match services {
+        RpcServices::EthSepolia(Some(services)) => {
+            services.iter().cloned().map(RpcService::EthSepolia).collect()
+        }
+        RpcServices::EthSepolia(None) => [
+            EthSepoliaService::Alchemy,
+            EthSepoliaService::Ankr,
+            EthSepoliaService::BlockPi,
+            EthSepoliaService::PublicNode,
+        ]
+        .into_iter()
+        .map(RpcService::EthSepolia)
+        .collect(),
+        RpcServices::EthMainnet(Some(services)) => {
+            services.iter().cloned().map(RpcService::EthMainnet).collect()
+        }
+        RpcServices::EthMainnet(None) => [
+            EthMainnetService::Alchemy,
+            EthMainnetService::Ankr,
+            EthMainnetService::BlockPi,
+            EthMainnetService::Cloudflare,
+            EthMainnetService::PublicNode,
+        ]
+        .into_iter()
+        .map(RpcService::EthMainnet)
+        .collect(),
+        RpcServices::Custom { services, .. } => {
+            services.iter().cloned().map(RpcService::Custom).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_default_config() {
+        assert_eq!(Config::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_a_zero_timer_interval() {
+        let mut config = Config::default();
+        config.governance.timer_interval_seconds = 0;
+        assert_eq!(
+            config.validate(),
+            Err("timer_interval_seconds must be greater than zero".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_a_priority_fee_above_the_max_fee() {
+        let mut config = Config::default();
+        config.max_priority_fee_per_gas = config.max_fee_per_gas.clone() + 1_u64;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn durations_saturate_instead_of_overflowing() {
+        let mut config = Config::default();
+        config.governance.stuck_transaction_threshold_seconds = u64::MAX;
+        config.governance.timelock_seconds = Some(u64::MAX);
+        assert_eq!(config.stuck_transaction_threshold(), u64::MAX);
+        assert_eq!(config.timelock("test"), u64::MAX);
+    }
+}
@@ -0,0 +1,167 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ethers_core::abi::{Contract, Function, FunctionExt, Token};
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+
+use crate::config::read_config;
+use crate::eth_rpc::{
+    contract_call_request, eth_call, eth_call_batch, eth_transaction, parse_address,
+};
+use crate::pending_transaction::PendingTransaction;
+use crate::transaction::TransactionRequest;
+use crate::{CONTRACTS, TARGET_CONTRACT};
+
+/// Name under which the contract that proposal results are written to is registered.
+pub const TARGET_CONTRACT_NAME: &str = "target";
+// ABI the target contract is registered with on first install
+const TARGET_CONTRACT_ABI: &str = include_str!("../../../solidity/contract.json");
+
+/// A contract the canister can call, registered at runtime by name.
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct RegisteredContract {
+    pub name: String,
+    pub address: String,
+    pub chain_id: u64,
+    /// The contract ABI in the JSON format emitted by solc.
+    pub abi: String,
+}
+
+impl RegisteredContract {
+    pub fn parse_abi(&self) -> Result<Contract, String> {
+        serde_json::from_str::<Contract>(&self.abi)
+            .map_err(|e| format!("Invalid ABI for contract {}: {}", self.name, e))
+    }
+}
+
+impl Storable for RegisteredContract {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Validates and stores a contract, replacing any contract registered under the same name.
+pub fn insert_contract(contract: RegisteredContract) -> Result<(), String> {
+    if contract.name.is_empty() {
+        return Err("Contract name must not be empty".to_string());
+    }
+    parse_address(&contract.address).map_err(|e| e.to_string())?;
+    let chain_id = read_config(|c| c.chain_id);
+    if contract.chain_id != chain_id {
+        return Err(format!(
+            "Contract {} is on chain {}, but the canister only transacts on chain {}",
+            contract.name, contract.chain_id, chain_id
+        ));
+    }
+    contract.parse_abi()?;
+
+    CONTRACTS.with(|c| c.borrow_mut().insert(contract.name.clone(), contract));
+    Ok(())
+}
+
+/// Registers the bundled target contract unless a contract with that name already exists.
+pub fn register_default_contracts() {
+    if CONTRACTS.with(|c| c.borrow().contains_key(&TARGET_CONTRACT_NAME.to_string())) {
+        return;
+    }
+    insert_contract(RegisteredContract {
+        name: TARGET_CONTRACT_NAME.to_string(),
+        address: TARGET_CONTRACT.to_string(),
+        chain_id: read_config(|c| c.chain_id),
+        abi: TARGET_CONTRACT_ABI.to_string(),
+    })
+    .expect("Bundled target contract is invalid");
+}
+
+pub fn resolve_contract(contract_name: &str) -> Result<(RegisteredContract, Contract), String> {
+    let contract = CONTRACTS
+        .with(|c| c.borrow().get(&contract_name.to_string()))
+        .ok_or_else(|| format!("Contract {contract_name} is not registered"))?;
+    let abi = contract.parse_abi()?;
+    Ok((contract, abi))
+}
+
+/// Looks up a function by name, or by its full signature such as `transfer(address,uint256)`
+/// when the name is overloaded.
+pub fn resolve_function<'a>(abi: &'a Contract, function: &str) -> Result<&'a Function, String> {
+    match abi.functions_by_name(function).map(|v| &v[..]) {
+        Ok([f]) => Ok(f),
+        Ok(fs) => Err(format!(
+            "Found {} function overloads. Please pass one of the following: {}",
+            fs.len(),
+            fs.iter()
+                .map(|f| format!("{:?}", f.abi_signature()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        Err(_) => abi
+            .functions()
+            .find(|f| function == f.abi_signature())
+            .ok_or_else(|| format!("Function {function} not found")),
+    }
+}
+
+/// Calls a read-only function of a registered contract.
+pub async fn registered_contract_call(
+    contract_name: &str,
+    function: &str,
+    args: &[Token],
+    block_number: &str,
+    proposal_id: Option<u64>,
+) -> Result<Vec<Token>, String> {
+    let (contract, abi) = resolve_contract(contract_name)?;
+    // Fail with an error here, as `eth_call` panics on unknown functions
+    resolve_function(&abi, function)?;
+    Ok(eth_call(contract.address, &abi, function, args, block_number, proposal_id).await)
+}
+
+// Estimated size of one `eth_call` entry in a batch response, enough for a few words of output
+const CALL_RESPONSE_BYTES: u64 = 400;
+
+/// Calls a read-only function of a registered contract once per entry of `args`, batching
+/// the requests.
+pub async fn registered_contract_call_batch(
+    contract_name: &str,
+    function: &str,
+    args: &[Vec<Token>],
+    block_number: &str,
+    proposal_id: Option<u64>,
+) -> Result<Vec<Result<Vec<Token>, String>>, String> {
+    let (contract, abi) = resolve_contract(contract_name)?;
+    eth_call_batch(
+        &contract.address,
+        &abi,
+        function,
+        args,
+        block_number,
+        CALL_RESPONSE_BYTES,
+        proposal_id,
+    )
+    .await
+}
+
+/// Builds a transaction calling a function of a registered contract.
+pub fn registered_contract_request(
+    contract_name: &str,
+    function: &str,
+    args: &[Token],
+) -> Result<TransactionRequest, String> {
+    let (contract, abi) = resolve_contract(contract_name)?;
+    contract_call_request(contract.address, &abi, function, args)
+}
+
+/// Submits a transaction calling a function of a registered contract.
+pub async fn registered_contract_transaction(
+    contract_name: &str,
+    function: &str,
+    args: &[Token],
+    proposal_id: Option<u64>,
+) -> Result<PendingTransaction, String> {
+    let (contract, abi) = resolve_contract(contract_name)?;
+    eth_transaction(contract.address, &abi, function, args, proposal_id).await
+}
@@ -0,0 +1,153 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::CYCLES_USAGE;
+
+/// The kinds of EVM RPC calls the canister pays for.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RpcOperation {
+    BlockNumber,
+    GetBalance,
+    EthCall,
+    GetTransactionCount,
+    SendRawTransaction,
+    GetTransactionReceipt,
+    GetLogs,
+    GasPrice,
+}
+
+/// Cycles attached to EVM RPC calls, split into what was kept and what was refunded.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CyclesUsage {
+    pub calls: u64,
+    pub spent: u128,
+    pub refunded: u128,
+}
+
+impl CyclesUsage {
+    fn add(&mut self, other: &CyclesUsage) {
+        self.calls += other.calls;
+        self.spent += other.spent;
+        self.refunded += other.refunded;
+    }
+}
+
+impl Storable for CyclesUsage {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// The proposal (`None` for calls not made on behalf of a proposal) and operation that usage is
+/// recorded for.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CyclesUsageKey {
+    pub proposal_id: Option<u64>,
+    pub operation: RpcOperation,
+}
+
+impl Storable for CyclesUsageKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 128,
+        is_fixed_size: false,
+    };
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ProposalCyclesUsage {
+    pub proposal_id: u64,
+    pub total: CyclesUsage,
+    pub by_operation: Vec<(RpcOperation, CyclesUsage)>,
+}
+
+/// Breakdown of the cycles spent on EVM RPC calls since the canister was installed.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CyclesReport {
+    pub total: CyclesUsage,
+    pub by_operation: Vec<(RpcOperation, CyclesUsage)>,
+    pub by_proposal: Vec<ProposalCyclesUsage>,
+}
+
+/// Records the outcome of a single paid EVM RPC call.
+pub fn record_cycles(proposal_id: Option<u64>, operation: RpcOperation, attached: u128, refunded: u128) {
+    let usage = CyclesUsage {
+        calls: 1,
+        spent: attached.saturating_sub(refunded),
+        refunded,
+    };
+    let key = CyclesUsageKey {
+        proposal_id,
+        operation,
+    };
+    CYCLES_USAGE.with(|u| {
+        let mut u = u.borrow_mut();
+        let mut total = u.get(&key).unwrap_or_default();
+        total.add(&usage);
+        u.insert(key, total);
+    });
+}
+
+pub fn cycles_report() -> CyclesReport {
+    CYCLES_USAGE.with(|u| {
+        let mut total = CyclesUsage::default();
+        let mut by_operation: BTreeMap<RpcOperation, CyclesUsage> = BTreeMap::new();
+        let mut by_proposal: BTreeMap<u64, ProposalCyclesUsage> = BTreeMap::new();
+
+        for (key, usage) in u.borrow().iter() {
+            total.add(&usage);
+            by_operation.entry(key.operation).or_default().add(&usage);
+            if let Some(proposal_id) = key.proposal_id {
+                let entry = by_proposal
+                    .entry(proposal_id)
+                    .or_insert_with(|| ProposalCyclesUsage {
+                        proposal_id,
+                        total: CyclesUsage::default(),
+                        by_operation: Vec::new(),
+                    });
+                entry.total.add(&usage);
+                entry.by_operation.push((key.operation, usage));
+            }
+        }
+
+        CyclesReport {
+            total,
+            by_operation: by_operation.into_iter().collect(),
+            by_proposal: by_proposal.into_values().collect(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_keys_fit_their_bound() {
+        let key = CyclesUsageKey {
+            proposal_id: Some(u64::MAX),
+            operation: RpcOperation::GetTransactionReceipt,
+        };
+        let Bound::Bounded { max_size, .. } = CyclesUsageKey::BOUND else {
+            panic!("Usage keys are bounded");
+        };
+        assert!(key.to_bytes().len() <= max_size as usize);
+        assert_eq!(CyclesUsageKey::from_bytes(key.to_bytes()), key);
+    }
+}
@@ -0,0 +1,346 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ethers_core::abi::{Contract, Token};
+use ethers_core::types::Address;
+use ethers_core::utils::keccak256;
+use ic_cdk::api::time;
+use ic_cdk::println;
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+
+use crate::config::read_config;
+use crate::eth_rpc::{eth_call_batch, parse_address};
+use crate::execution_payload::find_contract;
+use crate::{ENS_NAMES, ENS_RECORDS, USER_PROFILES};
+
+// The ENS registry has the same address on mainnet and Sepolia
+const DEFAULT_ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+const ENS_RESPONSE_BYTES: u64 = 1000;
+const ENS_REFRESH_INTERVAL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+// Bounds the outcalls of a single timer run
+const MAX_REFRESHES_PER_RUN: usize = 10;
+
+/// The verified ENS primary name of an address.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EnsRecord {
+    /// Set only if the reverse record resolves back to the address.
+    pub name: Option<String>,
+    /// The `avatar` text record of `name`.
+    pub avatar: Option<String>,
+    pub resolved_at: u64,
+}
+
+impl Storable for EnsRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+fn registry_abi() -> Contract {
+    ethers_core::abi::parse_abi(&["function resolver(bytes32 node) view returns (address)"])
+        .expect("ENS registry ABI is valid")
+}
+
+fn resolver_abi() -> Contract {
+    ethers_core::abi::parse_abi(&[
+        "function addr(bytes32 node) view returns (address)",
+        "function name(bytes32 node) view returns (string)",
+        "function text(bytes32 node, string key) view returns (string)",
+    ])
+    .expect("ENS resolver ABI is valid")
+}
+
+/// EIP-137 namehash of a name. Names are only lowercased, not fully normalized.
+pub fn namehash(name: &str) -> [u8; 32] {
+    let name = name.to_lowercase();
+    name.rsplit('.')
+        .filter(|label| !label.is_empty())
+        .fold([0_u8; 32], |node, label| {
+            keccak256([&node[..], &keccak256(label.as_bytes())[..]].concat())
+        })
+}
+
+// Calls a view function at the latest block, without panicking on reverts or empty results
+async fn call(
+    contract: &str,
+    abi: &Contract,
+    function: &str,
+    args: Vec<Token>,
+) -> Result<Vec<Token>, String> {
+    eth_call_batch(
+        contract,
+        abi,
+        function,
+        &[args],
+        "latest",
+        ENS_RESPONSE_BYTES,
+        None,
+    )
+    .await?
+    .pop()
+    .ok_or_else(|| "Missing ENS response".to_string())?
+}
+
+// Resolver of a node, `None` if the node has none
+async fn resolver(node: [u8; 32]) -> Result<Option<String>, String> {
+    let registry =
+        read_config(|c| c.ens_registry.clone()).unwrap_or_else(|| DEFAULT_ENS_REGISTRY.to_string());
+    let tokens = call(
+        &registry,
+        &registry_abi(),
+        "resolver",
+        vec![Token::FixedBytes(node.to_vec())],
+    )
+    .await?;
+    match tokens.first() {
+        Some(Token::Address(address)) if !address.is_zero() => Ok(Some(format!("{address:?}"))),
+        Some(Token::Address(_)) => Ok(None),
+        other => Err(format!("Unexpected ENS resolver: {other:?}")),
+    }
+}
+
+async fn text_call(resolver: &str, function: &str, args: Vec<Token>) -> Result<String, String> {
+    match call(resolver, &resolver_abi(), function, args)
+        .await?
+        .first()
+    {
+        Some(Token::String(text)) => Ok(text.clone()),
+        other => Err(format!("Unexpected ENS {function} result: {other:?}")),
+    }
+}
+
+/// Forward-resolves an ENS name to its address.
+pub async fn resolve_name(name: &str) -> Result<Address, String> {
+    let node = namehash(name);
+    let resolver = resolver(node)
+        .await?
+        .ok_or_else(|| format!("ENS name {name} has no resolver"))?;
+    match call(
+        &resolver,
+        &resolver_abi(),
+        "addr",
+        vec![Token::FixedBytes(node.to_vec())],
+    )
+    .await?
+    .first()
+    {
+        Some(Token::Address(address)) if !address.is_zero() => Ok(*address),
+        _ => Err(format!("ENS name {name} does not resolve to an address")),
+    }
+}
+
+/// Accepts either an address or an ENS name and returns the address.
+pub async fn resolve_address(input: &str) -> Result<String, String> {
+    if let Ok(address) = parse_address(input) {
+        return Ok(format!("{address:?}"));
+    }
+    if !input.contains('.') {
+        return Err(format!("{input} is neither an address nor an ENS name"));
+    }
+    resolve_name(input)
+        .await
+        .map(|address| format!("{address:?}"))
+}
+
+/// Resolves a contract target given as an ENS name. Addresses, registered contract names and
+/// other input are returned unchanged, for the validation of the target to report on.
+pub async fn resolve_target(target: &str) -> Result<String, String> {
+    if !target.contains('.') || find_contract(target).is_ok() {
+        return Ok(target.to_string());
+    }
+    resolve_name(target)
+        .await
+        .map(|address| format!("{address:?}"))
+}
+
+/// Looks up the primary name of `address` and keeps it only if it resolves back to the address.
+pub async fn lookup(address: &str) -> Result<EnsRecord, String> {
+    let parsed = parse_address(address)?;
+    let mut record = EnsRecord {
+        name: None,
+        avatar: None,
+        resolved_at: time(),
+    };
+    let reverse_node = namehash(&format!("{}.addr.reverse", hex::encode(parsed)));
+    let Some(reverse_resolver) = resolver(reverse_node).await? else {
+        return Ok(record);
+    };
+    let name = text_call(
+        &reverse_resolver,
+        "name",
+        vec![Token::FixedBytes(reverse_node.to_vec())],
+    )
+    .await?;
+    if name.is_empty() || resolve_name(&name).await.ok() != Some(parsed) {
+        return Ok(record);
+    }
+
+    let node = namehash(&name);
+    if let Some(resolver) = resolver(node).await? {
+        record.avatar = text_call(
+            &resolver,
+            "text",
+            vec![
+                Token::FixedBytes(node.to_vec()),
+                Token::String("avatar".to_string()),
+            ],
+        )
+        .await
+        .ok()
+        .filter(|avatar| !avatar.is_empty());
+    }
+    record.name = Some(name);
+    Ok(record)
+}
+
+pub fn ens_record(address: &str) -> Option<EnsRecord> {
+    ENS_RECORDS.with(|records| records.borrow().get(&address.to_lowercase()))
+}
+
+// Stores the record of `address` and moves its name in the name index
+fn store_record(address: &str, record: EnsRecord) {
+    let address = address.to_lowercase();
+    let name = record.name.as_deref().map(str::to_lowercase);
+    let previous = ENS_RECORDS.with(|records| records.borrow_mut().insert(address.clone(), record));
+    ENS_NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let previous_name = previous.and_then(|r| r.name).map(|n| n.to_lowercase());
+        if let Some(previous_name) = previous_name.filter(|n| Some(n) != name.as_ref()) {
+            if names.get(&previous_name).as_ref() == Some(&address) {
+                names.remove(&previous_name);
+            }
+        }
+        if let Some(name) = name {
+            names.insert(name, address);
+        }
+    });
+}
+
+/// Resolves and stores the ENS record of an address.
+pub async fn refresh(address: &str) -> Result<EnsRecord, String> {
+    let record = lookup(address).await?;
+    store_record(address, record.clone());
+    Ok(record)
+}
+
+/// Refreshes the ENS records of profile addresses that were never resolved or are older than a
+/// day, a few per run. Failed lookups keep the previous record and are retried a day later.
+pub async fn refresh_stale_records() {
+    let now = time();
+    let stale = USER_PROFILES.with(|profiles| {
+        profiles
+            .borrow()
+            .iter()
+            .map(|(_, profile)| profile.address)
+            .filter(|address| {
+                ens_record(address).is_none_or(|record| {
+                    now.saturating_sub(record.resolved_at) >= ENS_REFRESH_INTERVAL_NANOS
+                })
+            })
+            .take(MAX_REFRESHES_PER_RUN)
+            .collect::<Vec<_>>()
+    });
+    for address in stale {
+        if let Err(e) = refresh(&address).await {
+            println!("Error resolving the ENS name of {}: {}", address, e);
+            let mut record = ens_record(&address).unwrap_or(EnsRecord {
+                name: None,
+                avatar: None,
+                resolved_at: now,
+            });
+            record.resolved_at = now;
+            store_record(&address, record);
+        }
+    }
+}
+
+/// The address whose verified ENS name is `name`, among the cached records.
+pub fn cached_address_of(name: &str) -> Option<String> {
+    ENS_NAMES.with(|names| names.borrow().get(&name.to_lowercase()))
+}
+
+pub fn name_index_is_empty() -> bool {
+    ENS_NAMES.with(|names| names.borrow().is_empty())
+}
+
+/// Indexes the names of the records stored before the name index existed.
+pub fn rebuild_name_index() {
+    ENS_RECORDS.with(|records| {
+        ENS_NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            for (address, record) in records.borrow().iter() {
+                if let Some(name) = record.name {
+                    names.insert(name.to_lowercase(), address);
+                }
+            }
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vectors from EIP-137 and the ENS documentation
+    #[test]
+    fn namehash_matches_known_values() {
+        assert_eq!(namehash(""), [0_u8; 32]);
+        assert_eq!(
+            hex::encode(namehash("eth")),
+            "93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+        assert_eq!(
+            hex::encode(namehash("foo.eth")),
+            "de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+        );
+        assert_eq!(
+            hex::encode(namehash("vitalik.eth")),
+            "ee6c4522aab0003e8d14cd40a6af439055fd2577951148c14b6cea9a53475835"
+        );
+    }
+
+    #[test]
+    fn namehash_lowercases_names() {
+        assert_eq!(namehash("Vitalik.ETH"), namehash("vitalik.eth"));
+    }
+
+    fn record(name: Option<&str>) -> EnsRecord {
+        EnsRecord {
+            name: name.map(str::to_string),
+            avatar: None,
+            resolved_at: 0,
+        }
+    }
+
+    #[test]
+    fn indexes_names_of_stored_records() {
+        let address = "0x00000000000000000000000000000000000000Aa";
+        store_record(address, record(Some("Alice.eth")));
+        assert_eq!(cached_address_of("alice.ETH"), Some(address.to_lowercase()));
+
+        store_record(address, record(Some("alicia.eth")));
+        assert_eq!(cached_address_of("alice.eth"), None);
+        assert_eq!(
+            cached_address_of("alicia.eth"),
+            Some(address.to_lowercase())
+        );
+
+        store_record(address, record(None));
+        assert_eq!(cached_address_of("alicia.eth"), None);
+    }
+
+    #[test]
+    fn keeps_names_that_moved_to_another_address() {
+        let first = "0x00000000000000000000000000000000000000b1";
+        let second = "0x00000000000000000000000000000000000000b2";
+        store_record(first, record(Some("bob.eth")));
+        store_record(second, record(Some("bob.eth")));
+        store_record(first, record(None));
+        assert_eq!(cached_address_of("bob.eth"), Some(second.to_string()));
+    }
+}
@@ -1,13 +1,23 @@
+use crate::contract_registry::{
+    registered_contract_call, registered_contract_call_batch, registered_contract_transaction,
+    resolve_function,
+    TARGET_CONTRACT_NAME,
+};
+use crate::cycles::{record_cycles, RpcOperation};
 use crate::declarations::evm_rpc::*;
-use crate::{ECDSA_KEY, ETH_CONTRACT};
+use crate::pending_transaction::PendingTransaction;
+use crate::signing_space::{derivation_path, space_of_proposal};
+use crate::transaction::{submit_transaction, TransactionRequest, TransactionType};
+use crate::config::{expand_rpc_services, read_config, Config};
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
 use candid::Nat;
-use ethers_core::abi::ethereum_types::{Address, U256, U64};
-use ethers_core::abi::{AbiDecode, Contract, FunctionExt, Token};
-use ethers_core::types::Bytes;
+use ethers_core::abi::ethereum_types::{Address, U256};
+use ethers_core::abi::{AbiDecode, Contract, Token};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
 use ethers_core::utils::keccak256;
 use hex::FromHexError;
 use ic_cdk::api::{
-    call::{call_with_payment, CallResult},
+    call::{call, call_with_payment128, msg_cycles_refunded128, CallResult},
     management_canister::ecdsa::{
         ecdsa_public_key, sign_with_ecdsa, EcdsaKeyId, EcdsaPublicKeyArgument,
         SignWithEcdsaArgument,
@@ -17,14 +27,20 @@ use k256::elliptic_curve::sec1::ToEncodedPoint;
 use k256::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
-// const CHAIN_ID: u128 = 1337;
-const CHAIN_ID: u128 = 11155111; // Sepolia
-const GAS: u128 = 300_000;
-const MAX_FEE_PER_GAS: u128 = 156_083_066_522_u128;
-const MAX_PRIORITY_FEE_PER_GAS: u128 = 3_000_000_000;
+// Response size estimates for the typed EVM RPC methods, also used to price these calls
+const TRANSACTION_COUNT_RESPONSE_BYTES: u64 = 512;
+const SEND_RAW_TRANSACTION_RESPONSE_BYTES: u64 = 512;
+const TRANSACTION_RECEIPT_RESPONSE_BYTES: u64 = 10_000;
+const LOGS_RESPONSE_BYTES: u64 = 50_000;
+// Upper bound for the body of a single JSON-RPC batch response
+const BATCH_MAX_RESPONSE_BYTES: u64 = 20_000;
+// Allowance for the HTTP headers of a batch response, which count towards its size limit
+const BATCH_HEADER_BYTES: u64 = 2_000;
+// Estimated size of one `eth_getBalance` entry in a batch response
+const BALANCE_RESPONSE_BYTES: u64 = 120;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct JsonRpcRequest {
@@ -46,42 +62,51 @@ struct JsonRpcResult {
     error: Option<JsonRpcError>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct JsonRpcBatchResult {
+    id: usize,
+    result: Option<String>,
+    error: Option<JsonRpcError>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct JsonRpcError {
     code: isize,
     message: String,
 }
 
-#[macro_export]
-macro_rules! include_abi {
-    ($file:expr $(,)?) => {{
-        match serde_json::from_str::<ethers_core::abi::Contract>(include_str!($file)) {
-            Ok(contract) => contract,
-            Err(err) => panic!("Error loading ABI contract {:?}: {}", $file, err),
-        }
-    }};
-}
-
 fn ecdsa_key_id() -> EcdsaKeyId {
     EcdsaKeyId {
         curve: ic_cdk::api::management_canister::ecdsa::EcdsaCurve::Secp256k1,
-        name: ECDSA_KEY.with(|key| key.borrow().clone()),
+        name: read_config(|c| c.ecdsa_key_name.clone()),
     }
 }
 
-async fn next_id() -> Nat {
-    let res: CallResult<(MultiGetTransactionCountResult,)> = call_with_payment(
-        crate::declarations::evm_rpc::evm_rpc.0,
+pub async fn next_id(proposal_id: Option<u64>) -> Nat {
+    let address = get_space_eth_address(space_of_proposal(proposal_id).as_deref()).await;
+    let services = read_config(|c| c.rpc_services.clone());
+    let cycles = multi_request_cost(
+        &services,
+        &json_rpc_request("eth_getTransactionCount", serde_json::json!([address, "latest"])),
+        TRANSACTION_COUNT_RESPONSE_BYTES,
+    )
+    .await
+    .unwrap_or_else(|err| ic_cdk::trap(&err));
+    let res: CallResult<(MultiGetTransactionCountResult,)> = call_evm_rpc(
         "eth_getTransactionCount",
         (
-            RpcServices::EthSepolia(Some(vec![EthSepoliaService::BlockPi])),
-            None::<RpcConfig>,
+            services,
+            Some(RpcConfig {
+                responseSizeEstimate: Some(TRANSACTION_COUNT_RESPONSE_BYTES),
+            }),
             GetTransactionCountArgs {
-                address: get_self_eth_address().await,
+                address,
                 block: BlockTag::Latest,
             },
         ),
-        2_000_000_000,
+        cycles,
+        RpcOperation::GetTransactionCount,
+        proposal_id,
     )
     .await;
     match res {
@@ -91,6 +116,60 @@ async fn next_id() -> Nat {
     }
 }
 
+fn json_rpc_request(method: &str, params: serde_json::Value) -> String {
+    serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}).to_string()
+}
+
+/// Asks the EVM RPC canister how many cycles a JSON-RPC request to `service` costs.
+async fn request_cost(
+    service: RpcService,
+    json_rpc_payload: &str,
+    max_response_bytes: u64,
+) -> Result<u128, String> {
+    let res: CallResult<(RequestCostResult,)> = call(
+        read_config(Config::evm_rpc_canister_id),
+        "requestCost",
+        (service, json_rpc_payload.to_string(), max_response_bytes),
+    )
+    .await;
+    match res {
+        Ok((RequestCostResult::Ok(cost),)) => {
+            u128::try_from(cost.0).map_err(|_| "Request cost does not fit in u128".to_string())
+        }
+        Ok((RequestCostResult::Err(err),)) => Err(format!("Failed to get request cost: {err:?}")),
+        Err(err) => Err(format!("Failed to get request cost: {err:?}")),
+    }
+}
+
+/// Prices a typed EVM RPC call, which is charged like one JSON-RPC request per provider.
+async fn multi_request_cost(
+    services: &RpcServices,
+    json_rpc_payload: &str,
+    max_response_bytes: u64,
+) -> Result<u128, String> {
+    let costs = futures::future::try_join_all(
+        expand_rpc_services(services)
+            .into_iter()
+            .map(|service| request_cost(service, json_rpc_payload, max_response_bytes)),
+    )
+    .await?;
+    Ok(costs.into_iter().sum())
+}
+
+/// Calls `method` on the EVM RPC canister with `cycles` attached and records how many of them
+/// were kept.
+async fn call_evm_rpc<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+    method: &str,
+    args: T,
+    cycles: u128,
+    operation: RpcOperation,
+    proposal_id: Option<u64>,
+) -> CallResult<R> {
+    let res = call_with_payment128(read_config(Config::evm_rpc_canister_id), method, args, cycles).await;
+    record_cycles(proposal_id, operation, cycles, msg_cycles_refunded128());
+    res
+}
+
 pub fn parse_address(address_str: &str) -> Result<Address, &'static str> {
     // Remove any leading or trailing whitespace
 
@@ -117,27 +196,14 @@ pub async fn eth_call(
     function_name: &str,
     args: &[Token],
     block_number: &str,
+    proposal_id: Option<u64>,
 ) -> Vec<Token> {
-    let f = match abi.functions_by_name(function_name).map(|v| &v[..]) {
-        Ok([f]) => f,
-        Ok(fs) => panic!(
-            "Found {} function overloads. Please pass one of the following: {}",
-            fs.len(),
-            fs.iter()
-                .map(|f| format!("{:?}", f.abi_signature()))
-                .collect::<Vec<_>>()
-                .join(", ")
-        ),
-        Err(_) => abi
-            .functions()
-            .find(|f| function_name == f.abi_signature())
-            .expect("Function not found"),
-    };
+    let f = resolve_function(abi, function_name).unwrap_or_else(|e| panic!("{e}"));
     let data = f
         .encode_input(args)
         .expect("Error while encoding input args");
     let json_rpc_payload = serde_json::to_string(&JsonRpcRequest {
-        id: next_id().await.0.try_into().unwrap(),
+        id: next_id(proposal_id).await.0.try_into().unwrap(),
         jsonrpc: "2.0".to_string(),
         method: "eth_call".to_string(),
         params: (
@@ -150,15 +216,16 @@ pub async fn eth_call(
     })
     .expect("Error while encoding JSON-RPC request");
 
-    let res: CallResult<(RequestResult,)> = call_with_payment(
-        crate::declarations::evm_rpc::evm_rpc.0,
+    let service = read_config(|c| c.rpc_service.clone());
+    let cycles = request_cost(service.clone(), &json_rpc_payload, 2048)
+        .await
+        .expect("Failed to price eth_call");
+    let res: CallResult<(RequestResult,)> = call_evm_rpc(
         "request",
-        (
-            RpcService::EthSepolia(EthSepoliaService::BlockPi),
-            json_rpc_payload,
-            2048_u64,
-        ),
-        2_000_000_000,
+        (service, json_rpc_payload, 2048_u64),
+        cycles,
+        RpcOperation::EthCall,
+        proposal_id,
     )
     .await;
 
@@ -179,132 +246,231 @@ pub async fn eth_transaction(
     abi: &Contract,
     function_name: &str,
     args: &[Token],
-) -> Result<String, String> {
-    let f = match abi.functions_by_name(function_name).map(|v| &v[..]) {
-        Ok([f]) => f,
-        Ok(fs) => panic!(
-            "Found {} function overloads. Please pass one of the following: {}",
-            fs.len(),
-            fs.iter()
-                .map(|f| format!("{:?}", f.abi_signature()))
-                .collect::<Vec<_>>()
-                .join(", ")
-        ),
-        Err(_) => abi
-            .functions()
-            .find(|f| function_name == f.abi_signature())
-            .expect("Function not found"),
-    };
+    proposal_id: Option<u64>,
+) -> Result<PendingTransaction, String> {
+    let request = contract_call_request(contract_address, abi, function_name, args)?;
+    submit_transaction(request, proposal_id).await
+}
+
+/// Builds an EIP-1559 transaction calling `function_name` without sending ETH.
+pub fn contract_call_request(
+    contract_address: String,
+    abi: &Contract,
+    function_name: &str,
+    args: &[Token],
+) -> Result<TransactionRequest, String> {
+    let f = resolve_function(abi, function_name)?;
     let data = f
         .encode_input(args)
         .expect("Error while encoding input args");
-    let signed_data = sign_transaction(SignRequest {
-        chain_id: CHAIN_ID.into(),
+    Ok(TransactionRequest {
         to: contract_address,
-        gas: GAS.into(),
-        max_fee_per_gas: MAX_FEE_PER_GAS.into(),
-        max_priority_fee_per_gas: MAX_PRIORITY_FEE_PER_GAS.into(),
         value: 0_u8.into(),
-        nonce: next_id().await,
-        data: Some(data.into()),
+        data: Some(to_hex(&data)),
+        tx_type: TransactionType::Eip1559,
+        access_list: Vec::new(),
     })
-    .await;
+}
 
-    let (res,): (MultiSendRawTransactionResult,) = call_with_payment(
-        crate::declarations::evm_rpc::evm_rpc.0,
+/// Sends `value` wei to `to` without calldata.
+#[allow(unused)]
+pub async fn eth_transfer(
+    to: String,
+    value: Nat,
+    proposal_id: Option<u64>,
+) -> Result<PendingTransaction, String> {
+    submit_transaction(
+        TransactionRequest {
+            to,
+            value,
+            data: None,
+            tx_type: TransactionType::Eip1559,
+            access_list: Vec::new(),
+        },
+        proposal_id,
+    )
+    .await
+}
+
+/// Signs `tx` with its own nonce and fees and broadcasts it, returning the transaction hash.
+pub async fn send_transaction(
+    tx: &PendingTransaction,
+    proposal_id: Option<u64>,
+) -> Result<String, String> {
+    let path = derivation_path(space_of_proposal(proposal_id).as_deref());
+    let signed_data = sign_transaction(&tx.to_typed_transaction()?, path).await;
+    // The hash is derived locally so that it is known even if the providers do not report it.
+    let tx_hash = to_hex(&keccak256(from_hex(&signed_data).unwrap()));
+
+    let services = read_config(|c| c.rpc_services.clone());
+    let cycles = multi_request_cost(
+        &services,
+        &json_rpc_request("eth_sendRawTransaction", serde_json::json!([signed_data])),
+        SEND_RAW_TRANSACTION_RESPONSE_BYTES,
+    )
+    .await?;
+    let (res,): (MultiSendRawTransactionResult,) = call_evm_rpc(
         "eth_sendRawTransaction",
         (
-            RpcServices::EthSepolia(Some(vec![
-                EthSepoliaService::PublicNode,
-                EthSepoliaService::BlockPi,
-                EthSepoliaService::Ankr,
-            ])),
-            None::<RpcConfig>,
+            services,
+            Some(RpcConfig {
+                responseSizeEstimate: Some(SEND_RAW_TRANSACTION_RESPONSE_BYTES),
+            }),
             signed_data.clone(),
         ),
-        2_000_000_000,
+        cycles,
+        RpcOperation::SendRawTransaction,
+        proposal_id,
     )
     .await
     .unwrap();
 
     match res {
         MultiSendRawTransactionResult::Consistent(SendRawTransactionResult::Ok(
-            SendRawTransactionStatus::Ok(txid),
-        )) => match txid {
-            Some(id) => Ok(id),
-            None => Err("Transaction ID was missing despite successful transaction status.".to_string()),
-        },
-        
+            SendRawTransactionStatus::Ok(_),
+        )) => Ok(tx_hash),
         other => Err(format!("call: {signed_data}, error: {:?}", other)),
     }
 }
 
-fn to_hex(data: &[u8]) -> String {
-    format!("0x{}", hex::encode(data))
+/// Fetches the receipt of a transaction, returning `None` while it has not been mined.
+pub async fn transaction_receipt(
+    tx_hash: &str,
+    proposal_id: Option<u64>,
+) -> Result<Option<TransactionReceipt>, String> {
+    let services = read_config(|c| c.rpc_services.clone());
+    let cycles = multi_request_cost(
+        &services,
+        &json_rpc_request("eth_getTransactionReceipt", serde_json::json!([tx_hash])),
+        TRANSACTION_RECEIPT_RESPONSE_BYTES,
+    )
+    .await?;
+    let res: CallResult<(MultiGetTransactionReceiptResult,)> = call_evm_rpc(
+        "eth_getTransactionReceipt",
+        (
+            services,
+            Some(RpcConfig {
+                responseSizeEstimate: Some(TRANSACTION_RECEIPT_RESPONSE_BYTES),
+            }),
+            tx_hash.to_string(),
+        ),
+        cycles,
+        RpcOperation::GetTransactionReceipt,
+        proposal_id,
+    )
+    .await;
+    match res {
+        Ok((MultiGetTransactionReceiptResult::Consistent(GetTransactionReceiptResult::Ok(
+            receipt,
+        )),)) => Ok(receipt),
+        Ok((other,)) => Err(format!("Failed to fetch receipt for {tx_hash}: {other:?}")),
+        Err(err) => Err(format!("Failed to fetch receipt for {tx_hash}: {err:?}")),
+    }
 }
 
-fn from_hex(data: &str) -> Result<Vec<u8>, FromHexError> {
-    hex::decode(&data[2..])
+/// Returns the current gas price as reported by the provider.
+pub async fn gas_price(proposal_id: Option<u64>) -> Result<Nat, String> {
+    let response = match rpc_request(
+        json_rpc_request("eth_gasPrice", serde_json::json!([])),
+        256,
+        RpcOperation::GasPrice,
+        proposal_id,
+    )
+    .await?
+    {
+        RequestResult::Ok(response) => response,
+        RequestResult::Err(err) => return Err(format!("Failed to fetch gas price: {err:?}")),
+    };
+    let json: JsonRpcResult =
+        serde_json::from_str(&response).map_err(|e| format!("Malformed response: {e}"))?;
+    if let Some(err) = json.error {
+        return Err(format!("JSON-RPC error code {}: {}", err.code, err.message));
+    }
+    hex_to_nat(&json.result.ok_or("Unexpected JSON response")?)
 }
 
-#[derive(Debug)]
-struct SignRequest {
-    pub chain_id: Nat,
-    pub to: String,
-    pub gas: Nat,
-    pub max_fee_per_gas: Nat,
-    pub max_priority_fee_per_gas: Nat,
-    /// ETH to send
-    pub value: Nat,
-    pub nonce: Nat,
-    pub data: Option<Bytes>,
+/// Fetches the logs emitted by `address` with the given first topic in a block range.
+pub async fn eth_get_logs(
+    address: &str,
+    topic: &str,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<LogEntry>, String> {
+    let services = read_config(|c| c.rpc_services.clone());
+    let cycles = multi_request_cost(
+        &services,
+        &json_rpc_request(
+            "eth_getLogs",
+            serde_json::json!([{
+                "address": [address],
+                "topics": [[topic]],
+                "fromBlock": format!("{from_block:#x}"),
+                "toBlock": format!("{to_block:#x}"),
+            }]),
+        ),
+        LOGS_RESPONSE_BYTES,
+    )
+    .await?;
+    let res: CallResult<(MultiGetLogsResult,)> = call_evm_rpc(
+        "eth_getLogs",
+        (
+            services,
+            Some(RpcConfig {
+                responseSizeEstimate: Some(LOGS_RESPONSE_BYTES),
+            }),
+            GetLogsArgs {
+                fromBlock: Some(BlockTag::Number(from_block.into())),
+                toBlock: Some(BlockTag::Number(to_block.into())),
+                addresses: vec![address.to_string()],
+                topics: Some(vec![vec![topic.to_string()]]),
+            },
+        ),
+        cycles,
+        RpcOperation::GetLogs,
+        None,
+    )
+    .await;
+    match res {
+        Ok((MultiGetLogsResult::Consistent(GetLogsResult::Ok(logs)),)) => Ok(logs),
+        Ok((other,)) => Err(format!("Failed to fetch logs of {address}: {other:?}")),
+        Err(err) => Err(format!("Failed to fetch logs of {address}: {err:?}")),
+    }
 }
 
-/// Computes a signature for an [EIP-1559](https://eips.ethereum.org/EIPS/eip-1559) transaction.
-async fn sign_transaction(req: SignRequest) -> String {
-    use ethers_core::types::transaction::eip1559::Eip1559TransactionRequest;
-    use ethers_core::types::Signature;
-
-    const EIP1559_TX_ID: u8 = 2;
+pub fn to_hex(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
 
-    let tx = Eip1559TransactionRequest {
-        chain_id: Some(nat_to_u64(&req.chain_id)),
-        from: None,
-        to: Some(
-            Address::from_str(&req.to)
-                .expect("failed to parse the destination address")
-                .into(),
-        ),
-        gas: Some(nat_to_u256(&req.gas)),
-        value: Some(nat_to_u256(&req.value)),
-        nonce: Some(nat_to_u256(&req.nonce)),
-        data: req.data,
-        access_list: Default::default(),
-        max_priority_fee_per_gas: Some(nat_to_u256(&req.max_priority_fee_per_gas)),
-        max_fee_per_gas: Some(nat_to_u256(&req.max_fee_per_gas)),
-    };
+pub fn from_hex(data: &str) -> Result<Vec<u8>, FromHexError> {
+    hex::decode(&data[2..])
+}
 
-    let mut unsigned_tx_bytes = tx.rlp().to_vec();
-    unsigned_tx_bytes.insert(0, EIP1559_TX_ID);
+/// Signs a transaction of any supported type and returns its hex encoded raw form.
+async fn sign_transaction(tx: &TypedTransaction, derivation_path: Vec<Vec<u8>>) -> String {
+    use ethers_core::types::Signature;
 
-    let txhash = keccak256(&unsigned_tx_bytes);
+    let txhash = tx.sighash();
 
-    let (pubkey, signature) = pubkey_and_signature(txhash.to_vec()).await;
+    let (pubkey, signature) =
+        pubkey_and_signature(txhash.as_bytes().to_vec(), derivation_path).await;
 
+    let parity = y_parity(txhash.as_bytes(), &signature, &pubkey);
+    let v = match tx {
+        // EIP-155 folds the chain ID into `v` for replay protection.
+        TypedTransaction::Legacy(_) => parity + 35 + 2 * read_config(|c| c.chain_id),
+        _ => parity,
+    };
     let signature = Signature {
-        v: y_parity(&txhash, &signature, &pubkey),
+        v,
         r: U256::from_big_endian(&signature[0..32]),
         s: U256::from_big_endian(&signature[32..64]),
     };
 
-    let mut signed_tx_bytes = tx.rlp_signed(&signature).to_vec();
-    signed_tx_bytes.insert(0, EIP1559_TX_ID);
-
-    format!("0x{}", hex::encode(&signed_tx_bytes))
+    format!("0x{}", hex::encode(tx.rlp_signed(&signature)))
 }
 
 /// Computes the parity bit allowing to recover the public key from the signature.
-fn y_parity(prehash: &[u8], sig: &[u8], pubkey: &[u8]) -> u64 {
+pub fn y_parity(prehash: &[u8], sig: &[u8], pubkey: &[u8]) -> u64 {
     use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
 
     let orig_key = VerifyingKey::from_sec1_bytes(pubkey).expect("failed to parse the pubkey");
@@ -325,18 +491,21 @@ fn y_parity(prehash: &[u8], sig: &[u8], pubkey: &[u8]) -> u64 {
     )
 }
 
-/// Returns the public key and a message signature for the specified principal.
-async fn pubkey_and_signature(message_hash: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+/// Returns the public key and a message signature for the key at `derivation_path`.
+pub async fn pubkey_and_signature(
+    message_hash: Vec<u8>,
+    derivation_path: Vec<Vec<u8>>,
+) -> (Vec<u8>, Vec<u8>) {
     // Fetch the pubkey and the signature concurrently to reduce latency.
     let (pubkey, response) = futures::join!(
         ecdsa_public_key(EcdsaPublicKeyArgument {
             canister_id: None,
-            derivation_path: vec![],
+            derivation_path: derivation_path.clone(),
             key_id: ecdsa_key_id()
         }),
         sign_with_ecdsa(SignWithEcdsaArgument {
             message_hash,
-            derivation_path: vec![],
+            derivation_path,
             key_id: ecdsa_key_id(),
         })
     );
@@ -345,44 +514,42 @@ async fn pubkey_and_signature(message_hash: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
         response.expect("failed to sign the message").0.signature,
     )
 }
-fn nat_to_u256(n: &Nat) -> U256 {
+pub fn nat_to_u256(n: &Nat) -> U256 {
     let be_bytes = n.0.to_bytes_be();
     U256::from_big_endian(&be_bytes)
 }
 
-fn nat_to_u64(n: &Nat) -> U64 {
-    let be_bytes = n.0.to_bytes_be();
-    U64::from_big_endian(&be_bytes)
-}
-
-pub async fn rpc_request_with_cycles(
-    cycles: u64,
-    arg1: String,
+/// Sends a raw JSON-RPC request, paying exactly the cycles quoted by the EVM RPC canister.
+pub async fn rpc_request(
+    json_rpc_payload: String,
     max_response_bytes: u64,
-) -> CallResult<(RequestResult,)> {
-    call_with_payment(
-        crate::declarations::evm_rpc::evm_rpc.0,
+    operation: RpcOperation,
+    proposal_id: Option<u64>,
+) -> Result<RequestResult, String> {
+    let service = read_config(|c| c.rpc_service.clone());
+    let cycles = request_cost(service.clone(), &json_rpc_payload, max_response_bytes).await?;
+    call_evm_rpc(
         "request",
-        (
-            RpcService::EthSepolia(EthSepoliaService::Alchemy),
-            arg1,
-            max_response_bytes,
-        ),
+        (service, json_rpc_payload, max_response_bytes),
         cycles,
+        operation,
+        proposal_id,
     )
     .await
+    .map(|(res,)| res)
+    .map_err(|err| format!("{err:?}"))
 }
 
 /// returns latest block number in `U256` and hex encoded form
-pub async fn latest_block_number() -> (U256, String) {
-    let RequestResult::Ok(response) = rpc_request_with_cycles(
-        1_000_000_000,
+pub async fn latest_block_number(proposal_id: Option<u64>) -> (U256, String) {
+    let RequestResult::Ok(response) = rpc_request(
         "{\"jsonrpc\":\"2.0\",\"method\":\"eth_blockNumber\",\"params\":[]}".into(),
         2000,
+        RpcOperation::BlockNumber,
+        proposal_id,
     )
     .await
     .expect("RPC failed")
-    .0
     else {
         panic!("oops")
     };
@@ -396,8 +563,8 @@ pub async fn latest_block_number() -> (U256, String) {
     (U256::from_big_endian(&result), hex_result)
 }
 
-pub async fn eth_balance_of(user: &str, block_number: &str) -> Nat {
-    let RequestResult::Ok(response) = rpc_request_with_cycles(1_000_000_000, format!("{{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBalance\",\"params\":[\"{user}\",\"{block_number}\"]}}"), 2000).await.expect("RPC failed").0 else {panic!("oops")};
+pub async fn eth_balance_of(user: &str, block_number: &str, proposal_id: Option<u64>) -> Nat {
+    let RequestResult::Ok(response) = rpc_request(format!("{{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBalance\",\"params\":[\"{user}\",\"{block_number}\"]}}"), 2000, RpcOperation::GetBalance, proposal_id).await.expect("RPC failed") else {panic!("oops")};
     let json: JsonRpcResult = serde_json::from_str(&response).expect("JSON was not well-formatted");
     if let Some(err) = json.error {
         panic!("JSON-RPC error code {}: {}", err.code, err.message);
@@ -413,16 +580,192 @@ pub async fn eth_balance_of(user: &str, block_number: &str) -> Nat {
     .unwrap()
 }
 
+/// Like `eth_balance_of`, but returns RPC and JSON-RPC errors instead of trapping.
+pub async fn try_eth_balance_of(
+    user: &str,
+    block_number: &str,
+    proposal_id: Option<u64>,
+) -> Result<Nat, String> {
+    let payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_getBalance",
+        "params": [user, block_number],
+    });
+    // Sized like a batch of one request, so that the response headers fit as well
+    let response = match rpc_request(
+        payload.to_string(),
+        batch_max_response_bytes(BALANCE_RESPONSE_BYTES, 1),
+        RpcOperation::GetBalance,
+        proposal_id,
+    )
+    .await?
+    {
+        RequestResult::Ok(response) => response,
+        RequestResult::Err(e) => return Err(format!("{e:?}")),
+    };
+    let json: JsonRpcResult = serde_json::from_str(&response).map_err(|e| e.to_string())?;
+    if let Some(err) = json.error {
+        return Err(format!("JSON-RPC error code {}: {}", err.code, err.message));
+    }
+    hex_to_nat(&json.result.ok_or("Unexpected JSON response")?)
+}
+
+// Number of requests whose responses fit into the body budget of a batch
+fn batch_size(response_bytes_per_request: u64) -> usize {
+    (BATCH_MAX_RESPONSE_BYTES / response_bytes_per_request.max(1)).max(1) as usize
+}
+
+// Response size limit of a batch holding `requests` requests, including its headers
+fn batch_max_response_bytes(response_bytes_per_request: u64, requests: usize) -> u64 {
+    BATCH_HEADER_BYTES.saturating_add(response_bytes_per_request.saturating_mul(requests as u64))
+}
+
+/// Sends `requests` as JSON-RPC batches, each holding as many requests as fit into
+/// `BATCH_MAX_RESPONSE_BYTES`, and returns the hex encoded results in request order.
+pub async fn batch_request(
+    requests: &[(&str, serde_json::Value)],
+    response_bytes_per_request: u64,
+    operation: RpcOperation,
+    proposal_id: Option<u64>,
+) -> Result<Vec<Result<String, String>>, String> {
+    let batches = futures::future::try_join_all(
+        requests
+            .chunks(batch_size(response_bytes_per_request))
+            .map(|batch| send_batch(batch, response_bytes_per_request, operation, proposal_id)),
+    )
+    .await?;
+    Ok(batches.into_iter().flatten().collect())
+}
+
+async fn send_batch(
+    batch: &[(&str, serde_json::Value)],
+    response_bytes_per_request: u64,
+    operation: RpcOperation,
+    proposal_id: Option<u64>,
+) -> Result<Vec<Result<String, String>>, String> {
+    let payload = serde_json::Value::Array(
+        batch
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+            })
+            .collect(),
+    )
+    .to_string();
+    let max_response_bytes = batch_max_response_bytes(response_bytes_per_request, batch.len());
+
+    let response = match rpc_request(payload, max_response_bytes, operation, proposal_id).await? {
+        RequestResult::Ok(response) => response,
+        RequestResult::Err(err) => return Err(format!("Batch request failed: {err:?}")),
+    };
+    // Providers reply with a single error object if they reject the batch as a whole.
+    let entries: Vec<JsonRpcBatchResult> = match serde_json::from_str(&response) {
+        Ok(entries) => entries,
+        Err(_) => {
+            let json: JsonRpcResult = serde_json::from_str(&response)
+                .map_err(|e| format!("Malformed batch response: {e}"))?;
+            let err = json.error.map_or("unknown error".to_string(), |e| e.message);
+            return Err(format!("Batch request rejected: {err}"));
+        }
+    };
+
+    // Responses may come back in any order, so they are matched to requests by ID.
+    let mut results: Vec<Result<String, String>> =
+        vec![Err("Missing response in batch".to_string()); batch.len()];
+    for entry in entries {
+        let Some(slot) = results.get_mut(entry.id) else {
+            continue;
+        };
+        *slot = match (entry.result, entry.error) {
+            (_, Some(err)) => Err(format!("JSON-RPC error code {}: {}", err.code, err.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err("Empty response in batch".to_string()),
+        };
+    }
+    Ok(results)
+}
+
+fn hex_to_nat(hex_value: &str) -> Result<Nat, String> {
+    let value = U256::from_str_radix(hex_value.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Invalid hex quantity {hex_value}: {e}"))?;
+    Nat::from_str(&value.to_string()).map_err(|e| e.to_string())
+}
+
+/// Fetches the ETH balances of many addresses at `block_number` using batched requests.
+pub async fn eth_balances_of(
+    users: &[String],
+    block_number: &str,
+    proposal_id: Option<u64>,
+) -> Result<Vec<(String, Result<Nat, String>)>, String> {
+    let requests = users
+        .iter()
+        .map(|user| ("eth_getBalance", serde_json::json!([user, block_number])))
+        .collect::<Vec<_>>();
+    let results = batch_request(
+        &requests,
+        BALANCE_RESPONSE_BYTES,
+        RpcOperation::GetBalance,
+        proposal_id,
+    )
+    .await?;
+    Ok(users
+        .iter()
+        .cloned()
+        .zip(results.into_iter().map(|r| r.and_then(|hex| hex_to_nat(&hex))))
+        .collect())
+}
+
+/// Calls the same contract function once per entry of `args` using batched requests.
+pub async fn eth_call_batch(
+    contract_address: &str,
+    abi: &Contract,
+    function_name: &str,
+    args: &[Vec<Token>],
+    block_number: &str,
+    response_bytes_per_call: u64,
+    proposal_id: Option<u64>,
+) -> Result<Vec<Result<Vec<Token>, String>>, String> {
+    let f = resolve_function(abi, function_name)?;
+    let requests = args
+        .iter()
+        .map(|args| {
+            let data = f
+                .encode_input(args)
+                .map_err(|e| format!("Error while encoding input args: {e}"))?;
+            Ok((
+                "eth_call",
+                serde_json::json!([{"to": contract_address, "data": to_hex(&data)}, block_number]),
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let results = batch_request(
+        &requests,
+        response_bytes_per_call,
+        RpcOperation::EthCall,
+        proposal_id,
+    )
+    .await?;
+    Ok(results
+        .into_iter()
+        .map(|r| {
+            let output = from_hex(&r?).map_err(|e| e.to_string())?;
+            f.decode_output(&output).map_err(|e| e.to_string())
+        })
+        .collect())
+}
+
 #[allow(unused)]
-pub async fn erc20_balance_of(user: &str, block_number: &str) -> Nat {
-    let Token::Uint(balance) = eth_call(
-        super::TARGET_CONTRACT.into(),
-        &ETH_CONTRACT.with(Rc::clone),
+pub async fn erc20_balance_of(user: &str, block_number: &str, proposal_id: Option<u64>) -> Nat {
+    let Token::Uint(balance) = registered_contract_call(
+        TARGET_CONTRACT_NAME,
         "balanceOf",
         &[Token::Address(parse_address(user).unwrap())],
         block_number,
+        proposal_id,
     )
     .await
+    .unwrap_or_else(|e| panic!("{e}"))
     .get(0)
     .unwrap()
     .clone() else {
@@ -432,47 +775,113 @@ pub async fn erc20_balance_of(user: &str, block_number: &str) -> Nat {
 }
 
 #[allow(unused)]
-pub async fn erc20_transfer_to(to: &str, amount: u128 ) -> Result<String, String> {
-    eth_transaction(
-        super::TARGET_CONTRACT.into(),
-        &ETH_CONTRACT.with(Rc::clone),
+pub async fn erc20_balances_of(
+    users: &[String],
+    block_number: &str,
+    proposal_id: Option<u64>,
+) -> Result<Vec<(String, Result<Nat, String>)>, String> {
+    let args = users
+        .iter()
+        .map(|user| Ok(vec![Token::Address(parse_address(user)?)]))
+        .collect::<Result<Vec<_>, &str>>()?;
+    let results = registered_contract_call_batch(
+        TARGET_CONTRACT_NAME,
+        "balanceOf",
+        &args,
+        block_number,
+        proposal_id,
+    )
+    .await?;
+    Ok(users
+        .iter()
+        .cloned()
+        .zip(results.into_iter().map(|r| match r?.first() {
+            Some(Token::Uint(balance)) => Nat::from_str(&balance.to_string()).map_err(|e| e.to_string()),
+            other => Err(format!("Unexpected balanceOf output: {other:?}")),
+        }))
+        .collect())
+}
+
+#[allow(unused)]
+pub async fn erc20_transfer_to(to: &str, amount: u128, proposal_id: Option<u64>) -> Result<PendingTransaction, String> {
+    registered_contract_transaction(
+        TARGET_CONTRACT_NAME,
         "transfer",
         &[
             Token::Address(parse_address(to).unwrap()),
             Token::Uint(amount.into()),
         ],
+        proposal_id,
     )
     .await
 }
 
 thread_local! {
-    static SELF_ETH_ADDRESS: RefCell<Option<String>> =
-        RefCell::new(None);
+    // Derived addresses by signing space, `None` being the root address
+    static SELF_ETH_ADDRESSES: RefCell<BTreeMap<Option<String>, String>> =
+        const { RefCell::new(BTreeMap::new()) };
 }
 
 pub async fn get_self_eth_address() -> String {
-    if SELF_ETH_ADDRESS.with(|maybe_address| maybe_address.borrow().is_none()) {
-        let (pubkey,) = ecdsa_public_key(EcdsaPublicKeyArgument {
-            canister_id: None,
-            derivation_path: vec![],
-            key_id: ecdsa_key_id(),
-        })
-        .await
-        .unwrap();
+    get_space_eth_address(None).await
+}
 
-        let key = PublicKey::from_sec1_bytes(&pubkey.public_key)
-            .expect("failed to parse the public key as SEC1");
-        let point = key.to_encoded_point(false);
-        // we re-encode the key to the decompressed representation.
-        let point_bytes = point.as_bytes();
-        assert_eq!(point_bytes[0], 0x04);
+/// Returns the address the canister signs from in `space`, or its root address for `None`.
+pub async fn get_space_eth_address(space: Option<&str>) -> String {
+    let space_key = space.map(str::to_string);
+    if let Some(address) =
+        SELF_ETH_ADDRESSES.with(|addresses| addresses.borrow().get(&space_key).cloned())
+    {
+        return address;
+    }
 
-        let hash = keccak256(&point_bytes[1..]);
+    let (pubkey,) = ecdsa_public_key(EcdsaPublicKeyArgument {
+        canister_id: None,
+        derivation_path: derivation_path(space),
+        key_id: ecdsa_key_id(),
+    })
+    .await
+    .unwrap();
 
-        let self_address =
-            ethers_core::utils::to_checksum(&Address::from_slice(&hash[12..32]), None);
-        SELF_ETH_ADDRESS.with(|maybe_address| *maybe_address.borrow_mut() = Some(self_address));
+    let key = PublicKey::from_sec1_bytes(&pubkey.public_key)
+        .expect("failed to parse the public key as SEC1");
+    let point = key.to_encoded_point(false);
+    // we re-encode the key to the decompressed representation.
+    let point_bytes = point.as_bytes();
+    assert_eq!(point_bytes[0], 0x04);
+
+    let hash = keccak256(&point_bytes[1..]);
+
+    let self_address = ethers_core::utils::to_checksum(&Address::from_slice(&hash[12..32]), None);
+    SELF_ETH_ADDRESSES.with(|addresses| {
+        addresses
+            .borrow_mut()
+            .insert(space_key, self_address.clone())
+    });
+    self_address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_fill_the_body_budget() {
+        assert_eq!(batch_size(BALANCE_RESPONSE_BYTES), 166);
+        assert_eq!(batch_size(BATCH_MAX_RESPONSE_BYTES * 2), 1);
     }
 
-    SELF_ETH_ADDRESS.with(|maybe_address| maybe_address.borrow().clone().unwrap())
+    #[test]
+    fn batch_limits_include_the_header_allowance() {
+        assert_eq!(
+            batch_max_response_bytes(BALANCE_RESPONSE_BYTES, 166),
+            21_920
+        );
+        assert_eq!(batch_max_response_bytes(400, 1), 2_400);
+        let full_batch = batch_size(BALANCE_RESPONSE_BYTES);
+        assert!(
+            batch_max_response_bytes(BALANCE_RESPONSE_BYTES, full_batch)
+                <= BATCH_MAX_RESPONSE_BYTES + BATCH_HEADER_BYTES
+        );
+    }
 }
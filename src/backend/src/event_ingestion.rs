@@ -0,0 +1,224 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ethers_core::abi::{Event, Log, RawLog, Token};
+use ethers_core::types::H256;
+use ic_cdk::api::time;
+use ic_cdk::println;
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use crate::contract_registry::resolve_contract;
+use crate::declarations::evm_rpc::LogEntry;
+use crate::eth_rpc::{eth_get_logs, from_hex, latest_block_number};
+use crate::{push_proposal, Proposal, EVENT_WATCHERS};
+
+// Maximum number of blocks scanned per watcher and timer tick
+const MAX_BLOCK_RANGE: u64 = 500;
+
+/// Turns events emitted by a registered contract into proposals. Event parameters are
+/// referenced by the names they have in the contract ABI.
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct EventWatcher {
+    pub name: String,
+    /// Name of the contract in the contract registry.
+    pub contract_name: String,
+    /// Name of the event in the contract ABI, e.g. `ProposalCreated`.
+    pub event: String,
+    pub proposal_type: String,
+    /// Number of blocks a log must be buried under before it is ingested, so that logs
+    /// from blocks that get reorged out are never turned into proposals.
+    pub confirmations: u64,
+    pub title_param: Option<String>,
+    pub description_param: Option<String>,
+    pub proposer_param: Option<String>,
+    /// Parameter holding the snapshot block, the block of the log is used if unset.
+    pub snapshot_block_param: Option<String>,
+    /// Parameter holding the voting duration in seconds.
+    pub duration_param: Option<String>,
+    pub default_duration_seconds: u64,
+    /// Scanning resumes from the block after this one.
+    pub last_processed_block: u64,
+}
+
+impl Storable for EventWatcher {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl EventWatcher {
+    /// Resolves the contract address and event definition of this watcher.
+    pub fn resolve_event(&self) -> Result<(String, Event), String> {
+        let (contract, abi) = resolve_contract(&self.contract_name)?;
+        let event = match abi.events_by_name(&self.event).map(|v| &v[..]) {
+            Ok([event]) => event.clone(),
+            Ok(_) => return Err(format!("Event {} is overloaded", self.event)),
+            Err(_) => return Err(format!("Event {} not found", self.event)),
+        };
+        Ok((contract.address, event))
+    }
+
+    fn to_proposal(&self, entry: &LogEntry, log: &Log) -> Result<Proposal, String> {
+        let block_number = entry
+            .blockNumber
+            .clone()
+            .ok_or("Log is missing its block number")?;
+        let snapshot_block = match &self.snapshot_block_param {
+            Some(param) => format!("{:#x}", uint_param(log, param)?),
+            None => format!("{:#x}", u64::try_from(block_number.0).map_err(|e| e.to_string())?),
+        };
+        let duration_seconds = match &self.duration_param {
+            Some(param) => uint_param(log, param)?,
+            None => self.default_duration_seconds,
+        };
+        // Any emitter controls the duration, which must not overflow the proposal end timestamp
+        duration_seconds
+            .checked_mul(1_000_000_000)
+            .and_then(|nanos| time().checked_add(nanos))
+            .ok_or_else(|| format!("Duration of {duration_seconds} seconds is too long"))?;
+        let title = match &self.title_param {
+            Some(param) => string_param(log, param)?,
+            None => format!(
+                "{} in {}",
+                self.event,
+                entry.transactionHash.clone().unwrap_or_default()
+            ),
+        };
+        let description = match &self.description_param {
+            Some(param) => string_param(log, param)?,
+            None => String::new(),
+        };
+        let proposer = match &self.proposer_param {
+            Some(param) => string_param(log, param)?,
+            None => String::new(),
+        };
+
+        Ok(Proposal::new(
+            title,
+            description,
+            self.proposal_type.clone(),
+            format!("{}:{}", self.contract_name, self.event),
+            proposer,
+            snapshot_block,
+            duration_seconds,
+        ))
+    }
+}
+
+fn find_param<'a>(log: &'a Log, name: &str) -> Result<&'a Token, String> {
+    log.params
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| &p.value)
+        .ok_or_else(|| format!("Event has no parameter {name}"))
+}
+
+fn string_param(log: &Log, name: &str) -> Result<String, String> {
+    Ok(match find_param(log, name)? {
+        Token::String(s) => s.clone(),
+        Token::Address(address) => ethers_core::utils::to_checksum(address, None),
+        other => other.to_string(),
+    })
+}
+
+fn uint_param(log: &Log, name: &str) -> Result<u64, String> {
+    match find_param(log, name)? {
+        Token::Uint(value) if *value <= u64::MAX.into() => Ok(value.as_u64()),
+        other => Err(format!("Parameter {name} is not a u64: {other:?}")),
+    }
+}
+
+fn decode_log(event: &Event, entry: &LogEntry) -> Result<Log, String> {
+    let topics = entry
+        .topics
+        .iter()
+        .map(|topic| {
+            let bytes = from_hex(topic).map_err(|e| e.to_string())?;
+            if bytes.len() != 32 {
+                return Err(format!("Invalid topic {topic}"));
+            }
+            Ok(H256::from_slice(&bytes))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let data = from_hex(&entry.data).map_err(|e| e.to_string())?;
+    event
+        .parse_log(RawLog { topics, data })
+        .map_err(|e| format!("Failed to decode log: {e}"))
+}
+
+/// Scans the next confirmed block range of a watcher and returns the IDs of the created proposals.
+async fn poll_watcher(watcher: &EventWatcher, latest_block: u64) -> Result<Vec<u64>, String> {
+    let confirmed_block = latest_block.saturating_sub(watcher.confirmations);
+    if confirmed_block <= watcher.last_processed_block {
+        return Ok(Vec::new());
+    }
+    let from_block = watcher.last_processed_block + 1;
+    let to_block = confirmed_block.min(watcher.last_processed_block + MAX_BLOCK_RANGE);
+
+    let (address, event) = watcher.resolve_event()?;
+    let topic = format!("{:#x}", event.signature());
+    let logs = eth_get_logs(&address, &topic, from_block, to_block).await?;
+
+    let mut proposal_ids = Vec::new();
+    for entry in logs.iter().filter(|entry| !entry.removed) {
+        let proposal = decode_log(&event, entry).and_then(|log| watcher.to_proposal(entry, &log));
+        match proposal {
+            Ok(proposal) => proposal_ids.push(push_proposal(proposal)),
+            Err(e) => println!(
+                "Skipping log {:?} of watcher {}: {}",
+                entry.transactionHash, watcher.name, e
+            ),
+        }
+    }
+
+    // Re-read the watcher, as it may have been replaced or removed while the logs were fetched
+    EVENT_WATCHERS.with(|w| {
+        let mut watchers = w.borrow_mut();
+        if let Some(mut current) = watchers.get(&watcher.name) {
+            current.last_processed_block = current.last_processed_block.max(to_block);
+            watchers.insert(watcher.name.clone(), current);
+        }
+    });
+
+    Ok(proposal_ids)
+}
+
+thread_local! {
+    static POLLING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Clears the polling flag when dropped, which also happens if a callback traps
+struct PollingGuard;
+
+impl Drop for PollingGuard {
+    fn drop(&mut self) {
+        POLLING.with(|p| p.set(false));
+    }
+}
+
+/// Creates proposals from the events of all configured watchers.
+pub async fn poll_event_watchers() {
+    let watchers = EVENT_WATCHERS.with(|w| w.borrow().iter().map(|(_, w)| w).collect::<Vec<_>>());
+    // A slow poll must not overlap with the next timer tick, as both would ingest the same logs
+    if watchers.is_empty() || POLLING.with(|p| p.replace(true)) {
+        return;
+    }
+    let _guard = PollingGuard;
+
+    let (latest_block, _) = latest_block_number(None).await;
+    for watcher in watchers {
+        match poll_watcher(&watcher, latest_block.as_u64()).await {
+            Ok(ids) if !ids.is_empty() => {
+                println!("Watcher {} created proposals {:?}", watcher.name, ids)
+            }
+            Ok(_) => {}
+            Err(e) => println!("Error polling watcher {}: {}", watcher.name, e),
+        }
+    }
+}
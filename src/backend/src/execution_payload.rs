@@ -0,0 +1,160 @@
+use candid::{CandidType, Deserialize, Nat};
+use ethers_core::abi::{Function, FunctionExt, StateMutability};
+
+use crate::contract_registry::{resolve_contract, resolve_function, RegisteredContract};
+use crate::ens::resolve_target;
+use crate::eth_rpc::{from_hex, to_hex};
+use crate::pending_transaction::PendingTransaction;
+use crate::safe::{execute_through_safe, validate_mode, SafeMode};
+use crate::transaction::{submit_transaction, TransactionRequest, TransactionType};
+use crate::CONTRACTS;
+
+/// How the calldata of an execution payload is given.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum PayloadCall {
+    /// A function by name or full signature, e.g. `transfer(address,uint256)`, with its hex
+    /// encoded ABI-encoded arguments.
+    Function { signature: String, args: String },
+    /// Hex encoded calldata including the function selector.
+    Calldata(String),
+}
+
+/// A contract call a proposal performs once it passes.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ExecutionPayload {
+    /// Address of a registered contract or the name it is registered under. Stored as the
+    /// address once the payload is validated.
+    pub target: String,
+    pub call: PayloadCall,
+    /// ETH sent with the call, in wei.
+    pub value: Nat,
+    /// Routes the call through the configured Safe instead of sending it from the canister's
+    /// address. The value is then paid by the Safe.
+    pub safe: Option<SafeMode>,
+}
+
+impl ExecutionPayload {
+    /// Replaces a target given as an ENS name with its address. Runs before `validate`, which
+    /// cannot make calls.
+    pub async fn resolve_ens_target(mut self) -> Result<Self, String> {
+        self.target = resolve_target(&self.target).await?;
+        Ok(self)
+    }
+
+    /// Checks the payload against the ABI of the target contract and returns it with the
+    /// target resolved to an address and the function given by its full signature.
+    pub fn validate(self) -> Result<Self, String> {
+        let contract = find_contract(&self.target)?;
+        let abi = contract.parse_abi()?;
+
+        let (function, args) = match &self.call {
+            PayloadCall::Function { signature, args } => {
+                let function = resolve_function(&abi, signature)?;
+                let args = from_hex(args).map_err(|e| format!("Invalid arguments: {e}"))?;
+                (function, args)
+            }
+            PayloadCall::Calldata(calldata) => {
+                let calldata =
+                    from_hex(calldata).map_err(|e| format!("Invalid calldata: {e}"))?;
+                if calldata.len() < 4 {
+                    return Err("Calldata is missing the function selector".to_string());
+                }
+                let function = abi
+                    .functions()
+                    .find(|f| f.short_signature() == calldata[..4])
+                    .ok_or_else(|| {
+                        format!(
+                            "Contract {} has no function with selector {}",
+                            contract.name,
+                            to_hex(&calldata[..4])
+                        )
+                    })?;
+                (function, calldata[4..].to_vec())
+            }
+        };
+        check_args(function, &args)?;
+        if let Some(mode) = self.safe {
+            validate_mode(mode)?;
+        }
+        if self.value > 0_u8 && function.state_mutability != StateMutability::Payable {
+            return Err(format!(
+                "Function {} is not payable but the payload sends ETH",
+                function.abi_signature()
+            ));
+        }
+
+        Ok(Self {
+            target: contract.address,
+            call: PayloadCall::Function {
+                signature: function.abi_signature(),
+                args: to_hex(&args),
+            },
+            value: self.value,
+            safe: self.safe,
+        })
+    }
+
+    pub fn calldata(&self) -> Result<Vec<u8>, String> {
+        match &self.call {
+            PayloadCall::Function { signature, args } => {
+                let selector = &ethers_core::utils::keccak256(signature.as_bytes())[..4];
+                let args = from_hex(args).map_err(|e| format!("Invalid arguments: {e}"))?;
+                Ok([selector, &args].concat())
+            }
+            PayloadCall::Calldata(calldata) => {
+                from_hex(calldata).map_err(|e| format!("Invalid calldata: {e}"))
+            }
+        }
+    }
+
+    /// The call as an EIP-1559 transaction, as sent from the canister's or the Safe's address.
+    pub fn transaction_request(&self) -> Result<TransactionRequest, String> {
+        Ok(TransactionRequest {
+            to: self.target.clone(),
+            value: self.value.clone(),
+            data: Some(to_hex(&self.calldata()?)),
+            tx_type: TransactionType::Eip1559,
+            access_list: Vec::new(),
+        })
+    }
+
+    /// Signs and sends the call as an EIP-1559 transaction.
+    pub async fn execute(&self, proposal_id: u64) -> Result<PendingTransaction, String> {
+        if let Some(mode) = self.safe {
+            return execute_through_safe(self, mode, proposal_id).await;
+        }
+        submit_transaction(self.transaction_request()?, Some(proposal_id)).await
+    }
+}
+
+/// Looks up a registered contract by name, falling back to its address.
+pub fn find_contract(target: &str) -> Result<RegisteredContract, String> {
+    if let Ok((contract, _)) = resolve_contract(target) {
+        return Ok(contract);
+    }
+    CONTRACTS
+        .with(|c| {
+            c.borrow()
+                .iter()
+                .map(|(_, contract)| contract)
+                .find(|contract| contract.address.eq_ignore_ascii_case(target))
+        })
+        .ok_or_else(|| format!("Target {target} is not a registered contract"))
+}
+
+// Rejects arguments that do not decode to the function inputs or carry trailing bytes
+fn check_args(function: &Function, args: &[u8]) -> Result<(), String> {
+    let tokens = function
+        .decode_input(args)
+        .map_err(|e| format!("Arguments do not match {}: {e}", function.abi_signature()))?;
+    let encoded = function
+        .encode_input(&tokens)
+        .map_err(|e| format!("Arguments do not match {}: {e}", function.abi_signature()))?;
+    if encoded[4..] != *args {
+        return Err(format!(
+            "Arguments are not a canonical encoding for {}",
+            function.abi_signature()
+        ));
+    }
+    Ok(())
+}
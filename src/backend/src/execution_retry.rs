@@ -0,0 +1,124 @@
+use candid::{CandidType, Deserialize};
+use ic_cdk::api::time;
+
+use crate::config::read_config;
+use crate::PROPOSALS;
+
+/// A failed attempt to execute a proposal.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ExecutionFailure {
+    pub timestamp: u64,
+    pub error: String,
+}
+
+/// Stores the error of a failed execution attempt and schedules the next retry, unless the
+/// proposal has used up its automatic attempts.
+pub fn record_execution_failure(proposal_id: u64, error: String) {
+    let now = time();
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+            proposal.failed_executions.push(ExecutionFailure {
+                timestamp: now,
+                error,
+            });
+            let failed_attempts = proposal.failed_executions.len() as u32;
+            proposal.next_execution_attempt = read_config(|c| {
+                (failed_attempts < c.max_execution_attempts())
+                    .then(|| now.saturating_add(c.execution_retry_delay(failed_attempts)))
+            });
+        }
+    });
+}
+
+/// Stores the error of an execution that is not retried automatically. An admin can still
+/// retry it with `retry_proposal_execution`.
+pub fn stop_execution(proposal_id: u64, error: String) {
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+            proposal.failed_executions.push(ExecutionFailure {
+                timestamp: time(),
+                error,
+            });
+            proposal.next_execution_attempt = None;
+        }
+    });
+}
+
+/// Reopens the execution of a proposal whose transaction reverted or was cancelled, counting it
+/// as a failed attempt so that it is retried. Batches track the receipts of their actions instead.
+pub fn record_transaction_receipt(
+    proposal_id: u64,
+    tx_hash: &str,
+    succeeded: bool,
+    cancelled: bool,
+) {
+    if succeeded && !cancelled {
+        return;
+    }
+    let reopened = PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        let proposal = proposals
+            .iter_mut()
+            .find(|p| p.id == proposal_id && p.action_batch.is_none() && p.is_executed)?;
+        proposal.is_executed = false;
+        Some(())
+    });
+    if reopened.is_some() {
+        let outcome = if cancelled {
+            "was cancelled"
+        } else {
+            "reverted"
+        };
+        record_execution_failure(proposal_id, format!("Transaction {tx_hash} {outcome}"));
+    }
+}
+
+/// IDs of the closed proposals whose scheduled retry is due.
+pub fn proposals_due_for_retry() -> Vec<u64> {
+    let now = time();
+    PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .filter(|p| !p.is_executed && !p.is_vetoed && !p.execution_in_progress)
+            .filter(|p| p.next_execution_attempt.is_some_and(|t| t <= now))
+            .map(|p| p.id)
+            .collect()
+    })
+}
+
+// Clears the in-progress flag of an execution when dropped, which also happens if a callback
+// traps. An attempt dropped before its outcome was recorded is counted as failed.
+pub struct ExecutionGuard {
+    proposal_id: u64,
+    completed: bool,
+}
+
+impl ExecutionGuard {
+    pub fn new(proposal_id: u64) -> Self {
+        Self {
+            proposal_id,
+            completed: false,
+        }
+    }
+
+    pub fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for ExecutionGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            record_execution_failure(self.proposal_id, "Execution trapped".to_string());
+        }
+        PROPOSALS.with(|proposals| {
+            let mut proposals = proposals.borrow_mut();
+            if let Some(proposal) = proposals.iter_mut().find(|p| p.id == self.proposal_id) {
+                proposal.execution_in_progress = false;
+            }
+        });
+    }
+}
@@ -1,29 +1,70 @@
+mod action_batch;
+mod address_cache;
+mod attestation;
+mod canister_call;
+mod config;
+mod contract_registry;
+mod cycles;
 mod declarations;
+mod ens;
 mod eth_rpc;
+mod event_ingestion;
+mod execution_payload;
+mod execution_retry;
+mod pending_transaction;
+mod proposal_threshold;
+mod roles;
+mod safe;
 mod service;
+mod signing_space;
+mod simulation;
+mod timelock;
+mod transaction;
+mod treasury;
 mod user_profile;
-
+mod wallet_link;
+
+use crate::action_batch::{ActionBatch, ActionResult};
+use crate::address_cache::CachedAddress;
+use crate::attestation::{attest_closed_proposals, ResultAttestation};
+use crate::canister_call::{CanisterCall, CanisterCallResult};
+use crate::config::{read_config, set_config, CanisterArg, Config};
+use crate::contract_registry::{
+    insert_contract, register_default_contracts, registered_contract_transaction,
+    RegisteredContract, TARGET_CONTRACT_NAME,
+};
+use crate::cycles::{CyclesReport, CyclesUsage, CyclesUsageKey};
+use crate::ens::{name_index_is_empty, rebuild_name_index, refresh_stale_records, EnsRecord};
+use crate::proposal_threshold::{check_proposal_threshold, SubmitError};
+use crate::roles::{require_role, Role, RoleChange, RoleSet};
+use crate::safe::SignedSafeTransaction;
+use crate::signing_space::space_of_type;
+use crate::simulation::SimulationResult;
+use crate::service::get_voting_powers::VoterPower;
+use crate::timelock::{proposals_due_for_execution, ProposalSchedule};
+use crate::treasury::{TransferRecord, TreasuryBalance, TreasuryTransfer};
 use crate::eth_rpc::eth_balance_of;
-use candid::{CandidType, Deserialize, Nat};
-use eth_rpc::{eth_transaction, get_self_eth_address, latest_block_number};
-use ethers_core::abi::{Contract, Token};
+use crate::event_ingestion::{poll_event_watchers, EventWatcher};
+use crate::execution_payload::ExecutionPayload;
+use crate::execution_retry::{
+    proposals_due_for_retry, record_execution_failure, ExecutionFailure, ExecutionGuard,
+};
+use candid::{CandidType, Deserialize, Nat, Principal};
+use eth_rpc::{get_self_eth_address, latest_block_number, to_hex};
+use ethers_core::abi::Token;
 use ic_cdk_macros::export_candid;
+use pending_transaction::{check_pending_transactions, PendingTransaction};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 use std::cell::RefCell;
-use std::rc::Rc;
-use user_profile::UserProfile;
+use wallet_link::LinkChallenge;
+use user_profile::{address_index_is_empty, display_name, rebuild_address_index, UserProfile};
 
 use ic_cdk::api::{caller, time};
 use ic_cdk::{init, post_upgrade, println, query, update};
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
 
 pub const TARGET_CONTRACT: &str = "0x2036081922cf3124E9f13b3a3a4bE55410C80D95";
-// Load relevant ABIs (Ethereum equivalent of Candid interfaces)
-thread_local! {
-    pub static ETH_CONTRACT: Rc<Contract> = Rc::new(include_abi!("../../../solidity/contract.json"));
-}
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -37,14 +78,113 @@ thread_local! {
         )
     );
 
+    // Verified ENS names of profile addresses, keyed by lowercase address
+    static ENS_RECORDS: RefCell<StableBTreeMap<String, EnsRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    // Lowercase address of each verified ENS name in `ENS_RECORDS`, keyed by lowercase name
+    static ENS_NAMES: RefCell<StableBTreeMap<String, String, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+
+    // Addresses linked to principals with a signed message instead of SIWE, keyed by principal text
+    static LINKED_ADDRESSES: RefCell<StableBTreeMap<String, String, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    // Principal that claimed each lowercase Ethereum address in its profile
+    static ADDRESS_INDEX: RefCell<StableBTreeMap<String, String, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
+    // Contracts the canister can interact with, keyed by their registered name
+    static CONTRACTS: RefCell<StableBTreeMap<String, RegisteredContract, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+        )
+    );
+
+    // Contract events that are turned into proposals, keyed by watcher name
+    static EVENT_WATCHERS: RefCell<StableBTreeMap<String, EventWatcher, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
+        )
+    );
+
+    static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+            Config::default(),
+        )
+        .expect("failed to initialize the config cell")
+    );
+
+    // Roles granted to principals, keyed by principal text
+    static ROLES: RefCell<StableBTreeMap<String, RoleSet, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
+        )
+    );
+
+    // Every grant and revocation of a role, in order
+    static ROLE_CHANGES: RefCell<StableBTreeMap<u64, RoleChange, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    // Signed results of closed proposals, keyed by proposal ID
+    static ATTESTATIONS: RefCell<StableBTreeMap<u64, ResultAttestation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    // Cycles spent on EVM RPC calls, per proposal and operation
+    static CYCLES_USAGE: RefCell<StableBTreeMap<CyclesUsageKey, CyclesUsage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    // Outgoing treasury transfers, in the order they were sent
+    static TRANSFERS: RefCell<StableBTreeMap<u64, TransferRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    // Highest proposal ID handed out, so that IDs are not reused once the proposals on the heap
+    // are dropped by an upgrade
+    static LAST_PROPOSAL_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+            0,
+        )
+        .expect("failed to initialize the proposal ID cell")
+    );
+
     static VOTES: RefCell<HashMap<u64, HashMap<String, bool>>> = RefCell::new(HashMap::new());
+    // Addresses that cast a counter-vote against a queued proposal
+    static VETOES: RefCell<HashMap<u64, HashSet<String>>> = RefCell::new(HashMap::new());
+    // Holders backing the next proposal of a principal below the proposal threshold
+    static SPONSORSHIPS: RefCell<HashMap<Principal, Vec<Principal>>> = RefCell::new(HashMap::new());
+    // Addresses linked to principals through SIWE, so that actions skip the provider call
+    static ADDRESS_CACHE: RefCell<HashMap<Principal, CachedAddress>> = RefCell::new(HashMap::new());
+    // Messages issued to principals that are linking an address
+    static LINK_CHALLENGES: RefCell<HashMap<Principal, LinkChallenge>> = RefCell::new(HashMap::new());
     static PROPOSALS: RefCell<Vec<Proposal>> = RefCell::new(Vec::new());
-    static ECDSA_KEY: RefCell<String> = RefCell::new(String::default());
 }
 
-// Duration for periodic checks of proposals
-const TIMER_INTERVAL: Duration = Duration::from_secs(60);
-
 #[derive(CandidType, Deserialize, Clone, Debug)]
 struct Proposal {
     id: u64,
@@ -53,6 +193,10 @@ struct Proposal {
     proposal_type: String,
     submitter: String,
     submitter_eth_address: String,
+    // Address of the holder who backed a submitter below the proposal threshold
+    sponsor: Option<String>,
+    // Profile name of the submitter, filled in when proposals are read
+    submitter_name: Option<String>,
     proposal_start_timestamp: u64,
     proposal_end_timestamp: u64,
     is_open: bool,
@@ -61,6 +205,127 @@ struct Proposal {
     no_votes: Nat,
     block_height: String,
     eth_transaction_hash: Option<String>,
+    // Every hash broadcast for the execution, including fee-bumped replacements and cancellations
+    eth_transaction_hashes: Vec<String>,
+    pending_transaction: Option<PendingTransaction>,
+    // Contract call performed if the proposal passes, instead of recording the result on the target contract
+    execution_payload: Option<ExecutionPayload>,
+    // Funds sent from the treasury if the proposal passes
+    treasury_transfer: Option<TreasuryTransfer>,
+    // Contract calls performed in order if the proposal passes, with the outcome of each
+    action_batch: Option<ActionBatch>,
+    action_results: Vec<ActionResult>,
+    // Inter-canister call performed if the proposal passes, with the outcome of the latest attempt
+    canister_call: Option<CanisterCall>,
+    canister_call_result: Option<CanisterCallResult>,
+    // Safe transactions the canister signed while executing, for cosigners to verify
+    safe_transactions: Vec<SignedSafeTransaction>,
+    // Signing space of the proposal type on submission, kept so that config changes don't move
+    // the execution to another address
+    signing_space: Option<String>,
+    // Set while an execution attempt waits for its transaction, so that attempts never overlap
+    execution_in_progress: bool,
+    failed_executions: Vec<ExecutionFailure>,
+    // When the timer retries a failed execution, `None` if no retry is scheduled
+    next_execution_attempt: Option<u64>,
+    // Set when voting ends, the proposal can be vetoed until then
+    execution_eta: Option<u64>,
+    is_vetoed: bool,
+    veto_votes: Nat,
+}
+
+impl Proposal {
+    /// Creates an open proposal starting now. The ID is assigned by `push_proposal`.
+    fn new(
+        title: String,
+        description: String,
+        proposal_type: String,
+        submitter: String,
+        submitter_eth_address: String,
+        block_height: String,
+        duration_seconds: u64,
+    ) -> Self {
+        let proposal_start_timestamp = time();
+        let duration_in_nanoseconds = duration_seconds.saturating_mul(1_000_000_000);
+        let proposal_end_timestamp =
+            proposal_start_timestamp.saturating_add(duration_in_nanoseconds);
+        println!("Proposal start timestamp: {}", proposal_start_timestamp);
+        println!(
+            "Computed proposal end timestamp: {}",
+            proposal_end_timestamp
+        );
+        let signing_space = space_of_type(&proposal_type);
+
+        Proposal {
+            id: 0,
+            title,
+            description,
+            proposal_type,
+            submitter,
+            submitter_eth_address,
+            proposal_start_timestamp,
+            proposal_end_timestamp,
+            is_open: true,
+            is_executed: false,
+            yes_votes: 0_usize.into(), // No votes yet
+            no_votes: 0_usize.into(),  // No votes yet
+            block_height,
+            eth_transaction_hash: None,
+            eth_transaction_hashes: Vec::new(),
+            pending_transaction: None,
+            execution_payload: None,
+            treasury_transfer: None,
+            action_batch: None,
+            action_results: Vec::new(),
+            canister_call: None,
+            canister_call_result: None,
+            sponsor: None,
+            submitter_name: None,
+            safe_transactions: Vec::new(),
+            signing_space,
+            execution_in_progress: false,
+            failed_executions: Vec::new(),
+            next_execution_attempt: None,
+            execution_eta: None,
+            is_vetoed: false,
+            veto_votes: 0_usize.into(),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.yes_votes > self.no_votes
+    }
+
+    /// The summary written to the target contract when a proposal without payload executes.
+    fn result_summary(&self) -> String {
+        let total_votes = self.yes_votes.clone() + self.no_votes.clone();
+        let zero = candid::Nat::from(0u64);
+
+        let yes_percentage = if total_votes > zero {
+            let hundred = candid::Nat::from(100u64);
+            ((self.yes_votes.clone() * hundred) / total_votes).to_string()
+        } else {
+            "0".to_string() // If no votes have been cast, set the percentage to 0%
+        };
+
+        format!("{}: Proposal {}: {}% yes", ic_cdk::id(), self.id, yes_percentage)
+    }
+}
+
+/// Stores a new proposal and returns its ID.
+fn push_proposal(mut proposal: Proposal) -> u64 {
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        let new_id = LAST_PROPOSAL_ID.with(|last| {
+            let mut last = last.borrow_mut();
+            let new_id = (*last.get()).max(proposals.len() as u64) + 1;
+            last.set(new_id).expect("failed to write the proposal ID to stable memory");
+            new_id
+        });
+        proposal.id = new_id;
+        proposals.push(proposal);
+        new_id // Returning the ID of the new proposal
+    })
 }
 
 #[update]
@@ -69,20 +334,36 @@ async fn submit_proposal(
     description: String,
     proposal_type: String,
     duration_seconds: u64,
-) -> u64 {
+    execution_payload: Option<ExecutionPayload>,
+) -> Result<u64, SubmitError> {
+    require_role(Role::Proposer)?;
+    let execution_payload = match execution_payload {
+        Some(payload) => Some(
+            payload
+                .resolve_ens_target()
+                .await
+                .and_then(ExecutionPayload::validate)
+                .map_err(|e| format!("Invalid execution payload: {e}"))?,
+        ),
+        None => None,
+    };
+    let mut proposal = new_caller_proposal(title, description, proposal_type, duration_seconds).await?;
+    proposal.execution_payload = execution_payload;
+    Ok(push_proposal(proposal))
+}
+
+/// Creates a proposal submitted by the caller, snapshotting voting power at the latest block.
+/// Fails if neither the caller nor a sponsor meets the proposal threshold.
+async fn new_caller_proposal(
+    title: String,
+    description: String,
+    proposal_type: String,
+    duration_seconds: u64,
+) -> Result<Proposal, SubmitError> {
     let submitter = caller().to_text();
     // Initialize submitter_eth_address as an empty string or an appropriate default value
     let mut submitter_eth_address: String = "".to_string();
 
-    let proposal_start_timestamp = time();
-    let duration_in_nanoseconds = duration_seconds * 1_000_000_000;
-    let proposal_end_timestamp = proposal_start_timestamp + duration_in_nanoseconds;
-    println!("Proposal start timestamp: {}", proposal_start_timestamp);
-    println!(
-        "Computed proposal end timestamp: {}",
-        proposal_end_timestamp
-    );
-
     // Attempt to get the address asynchronously
     match service::save_my_profile::get_address().await {
         Ok(address) => {
@@ -95,35 +376,29 @@ async fn submit_proposal(
         }
     }
 
-    let (_, block_height) = latest_block_number().await;
-    PROPOSALS.with(|proposals| {
-        let mut proposals = proposals.borrow_mut();
-        let new_id = proposals.len() as u64 + 1; // Simple ID generation
-        let proposal = Proposal {
-            id: new_id,
-            title,
-            description,
-            proposal_type,
-            submitter,
-            submitter_eth_address, // This will be empty or contain the address from get_address()
-            proposal_start_timestamp,
-            proposal_end_timestamp,
-            is_open: true,
-            is_executed: false,
-            yes_votes: 0_usize.into(), // No votes yet
-            no_votes: 0_usize.into(),  // No votes yet
-            block_height,
-            eth_transaction_hash: None,
-        };
-
-        proposals.push(proposal);
-        new_id // Returning the ID of the new proposal
-    })
+    let (_, block_height) = latest_block_number(None).await;
+    let linked_address = Some(submitter_eth_address.as_str()).filter(|a| !a.is_empty());
+    let sponsor = check_proposal_threshold(caller(), linked_address, &block_height).await?;
+    let mut proposal = Proposal::new(
+        title,
+        description,
+        proposal_type,
+        submitter,
+        submitter_eth_address, // This will be empty or contain the address from get_address()
+        block_height,
+        duration_seconds,
+    );
+    proposal.sponsor = sponsor;
+    Ok(proposal)
 }
 
 #[query]
 fn get_proposals() -> Vec<Proposal> {
-    PROPOSALS.with(|proposals_ref| proposals_ref.borrow().clone())
+    let mut proposals = PROPOSALS.with(|proposals_ref| proposals_ref.borrow().clone());
+    for proposal in &mut proposals {
+        proposal.submitter_name = display_name(&proposal.submitter);
+    }
+    proposals
 }
 
 #[update]
@@ -184,7 +459,7 @@ async fn vote_on_proposal(proposal_id: u64, vote: bool) -> Result<(), String> {
         return Err("You have already voted on this proposal".to_string());
     }
 
-    let voting_power = eth_balance_of(&voter, &block_number).await;
+    let voting_power = eth_balance_of(&voter, &block_number, Some(proposal_id)).await;
 
     // Update the proposal's vote tally
     PROPOSALS.with(|proposals| {
@@ -211,53 +486,110 @@ async fn vote_on_proposal(proposal_id: u64, vote: bool) -> Result<(), String> {
 
 #[update]
 async fn execute_proposal(proposal_id: u64) -> Result<String, String> {
-    let eth_tx_summary = PROPOSALS.with(|proposals| {
+    require_role(Role::Executor)?;
+    attempt_execution(proposal_id, false).await
+}
+
+/// Makes one attempt to execute a proposal. Failures are recorded on the proposal and retried
+/// by the timer with backoff, `ignore_attempt_limit` allows retries once the attempts are used up.
+async fn attempt_execution(proposal_id: u64, ignore_attempt_limit: bool) -> Result<String, String> {
+    let max_attempts = read_config(Config::max_execution_attempts);
+    let (eth_tx_summary, execution_payload, treasury_transfer, action_batch, canister_call) = PROPOSALS.with(|proposals| {
         let mut proposals = proposals.borrow_mut();
         let proposal = proposals.iter_mut().find(|p| p.id == proposal_id)
             .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
         if proposal.is_executed {
             return Err(format!("Proposal {proposal_id} already executed"));
         }
-        proposal.is_executed = true;
-
-        let total_votes = proposal.yes_votes.clone() + proposal.no_votes.clone();
-        let zero = candid::Nat::from(0u64);
-
-        let yes_percentage = if total_votes > zero {
-            let hundred = candid::Nat::from(100u64);
-            ((proposal.yes_votes.clone() * hundred) / total_votes).to_string()
-        } else {
-            "0".to_string() // If no votes have been cast, set the percentage to 0%
-        };
+        if proposal.execution_in_progress {
+            return Err(format!("Proposal {proposal_id} is already being executed"));
+        }
+        if proposal.is_vetoed {
+            return Err(format!("Proposal {proposal_id} was vetoed"));
+        }
+        if !proposal.will_execute() {
+            return Err(format!("Proposal {proposal_id} did not pass, its payload is not executed"));
+        }
+        match proposal.execution_eta {
+            None => return Err(format!("Proposal {proposal_id} is still open for voting")),
+            Some(eta) if eta > time() => {
+                return Err(format!("Proposal {proposal_id} is timelocked until {eta}"))
+            }
+            Some(_) => {}
+        }
+        let failed_attempts = proposal.failed_executions.len() as u32;
+        if !ignore_attempt_limit && failed_attempts >= max_attempts {
+            return Err(format!(
+                "Proposal {proposal_id} failed to execute {failed_attempts} times, only an admin can retry it"
+            ));
+        }
+        proposal.execution_in_progress = true;
+        proposal.next_execution_attempt = None;
 
-        let eth_tx_summary = format!(
-            "{}: Proposal {}: {}% yes",
-            ic_cdk::id(),
-            proposal.id,
-            yes_percentage
-        );
+        let eth_tx_summary = proposal.result_summary();
         println!("Summary for proposal {}: {}", proposal_id, eth_tx_summary);
-        Ok(eth_tx_summary)
+        Ok((
+            eth_tx_summary,
+            proposal.execution_payload.clone(),
+            proposal.treasury_transfer.clone(),
+            proposal.action_batch.clone(),
+            proposal.canister_call.clone(),
+        ))
     })?;
+    let guard = ExecutionGuard::new(proposal_id);
+
+    // Canister calls complete within this call and return the hex encoded reply
+    if let Some(call) = canister_call {
+        let result = call.execute(proposal_id).await;
+        match &result {
+            Ok(_) => PROPOSALS.with(|proposals| {
+                let mut proposals = proposals.borrow_mut();
+                if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+                    proposal.is_executed = true;
+                }
+            }),
+            Err(e) => record_execution_failure(proposal_id, e.clone()),
+        }
+        guard.complete();
+        return result.map(|reply| to_hex(&reply));
+    }
 
     // Perform the Ethereum transaction and capture the transaction hash
-    let transaction_result = eth_transaction(
-        TARGET_CONTRACT.into(),
-        &ETH_CONTRACT.with(Rc::clone),
-        "storeString",
-        &[Token::String(eth_tx_summary.clone())],
-    ).await?;
-    
-
-    // Update the proposal with the Ethereum transaction hash if the transaction was successful
+    let transaction_result = match (execution_payload, treasury_transfer, action_batch) {
+        (Some(payload), _, _) => payload.execute(proposal_id).await,
+        (None, Some(transfer), _) => transfer.execute(proposal_id).await,
+        (None, None, Some(batch)) => batch.execute_next(proposal_id).await,
+        (None, None, None) => registered_contract_transaction(
+            TARGET_CONTRACT_NAME,
+            "storeString",
+            &[Token::String(eth_tx_summary.clone())],
+            Some(proposal_id),
+        ).await,
+    };
+    let transaction_result = match transaction_result {
+        Ok(transaction_result) => transaction_result,
+        Err(e) => {
+            record_execution_failure(proposal_id, e.clone());
+            guard.complete();
+            return Err(e);
+        }
+    };
+
+    // Update the proposal with the Ethereum transaction hash if the transaction was successful.
+    // Batches are executed once the receipts show that all their actions succeeded.
+    let tx_hash = transaction_result.hash.clone();
     PROPOSALS.with(|proposals| {
         let mut proposals = proposals.borrow_mut();
         if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
-            proposal.eth_transaction_hash = Some(transaction_result.clone());
+            proposal.is_executed = proposal.action_batch.is_none();
+            proposal.eth_transaction_hash = Some(tx_hash.clone());
+            proposal.eth_transaction_hashes.push(tx_hash.clone());
+            proposal.pending_transaction = Some(transaction_result);
         }
     });
+    guard.complete();
 
-    Ok(transaction_result)
+    Ok(tx_hash)
 }
 
 #[update]
@@ -267,59 +599,108 @@ async fn get_eth_address() -> String {
 
 #[update]
 async fn get_my_eth_balance() -> String {
-    eth_balance_of(&get_self_eth_address().await, "latest")
+    eth_balance_of(&get_self_eth_address().await, "latest", None)
         .await
         .to_string()
 }
 
 #[init]
-fn init(key_id: String) {
-    ECDSA_KEY.with(|key| {
-        *key.borrow_mut() = key_id;
-    });
+fn init(arg: CanisterArg) {
+    let CanisterArg::Init(args) = arg else {
+        ic_cdk::trap("Expected init arguments on install");
+    };
+    let target_contracts = args.target_contracts.clone().unwrap_or_default();
+    let config = Config::from_init_args(args);
+    if let Err(e) = config.validate() {
+        ic_cdk::trap(&format!("Invalid configuration: {e}"));
+    }
+    set_config(config);
+    register_default_contracts();
+    register_contracts(target_contracts);
 
     // Set up the timer to periodically check and execute proposals
-    ic_cdk_timers::set_timer_interval(TIMER_INTERVAL, || {
+    ic_cdk_timers::set_timer_interval(read_config(Config::timer_interval), || {
         ic_cdk::spawn(check_and_execute_proposals());
+        ic_cdk::spawn(poll_event_watchers());
     });
 }
 
 #[post_upgrade]
-fn post_upgrade(key_id: String) {
-    ECDSA_KEY.with(|key| {
-        *key.borrow_mut() = key_id;
-    });
+fn post_upgrade(arg: CanisterArg) {
+    let CanisterArg::Upgrade(args) = arg else {
+        ic_cdk::trap("Expected upgrade arguments on upgrade");
+    };
+    if let Some(args) = args {
+        let target_contracts = args.target_contracts.clone().unwrap_or_default();
+        let mut config = read_config(Config::clone);
+        config.apply_upgrade_args(args);
+        if let Err(e) = config.validate() {
+            ic_cdk::trap(&format!("Invalid configuration: {e}"));
+        }
+        set_config(config);
+        register_contracts(target_contracts);
+    }
+    register_default_contracts();
+    if address_index_is_empty() {
+        rebuild_address_index();
+    }
+    if name_index_is_empty() {
+        rebuild_name_index();
+    }
 
     // Re-setup the timer to continue periodic checks after an upgrade
-    ic_cdk_timers::set_timer_interval(TIMER_INTERVAL, || {
+    ic_cdk_timers::set_timer_interval(read_config(Config::timer_interval), || {
         ic_cdk::spawn(check_and_execute_proposals());
+        ic_cdk::spawn(poll_event_watchers());
     });
 }
 
+fn register_contracts(contracts: Vec<RegisteredContract>) {
+    for contract in contracts {
+        let name = contract.name.clone();
+        if let Err(e) = insert_contract(contract) {
+            ic_cdk::trap(&format!("Invalid target contract {name}: {e}"));
+        }
+    }
+}
+
 // Function to check and execute proposals if their end time has passed
 async fn check_and_execute_proposals() {
-    let mut ids_to_execute = Vec::new();
-
-    // Collect proposal IDs synchronously
+    // Close proposals whose voting period ended, which queues accepted ones behind their timelock
     PROPOSALS.with(|proposals_ref| {
         let mut proposals = proposals_ref.borrow_mut();
         for proposal in proposals.iter_mut() {
             if proposal.is_open && proposal.proposal_end_timestamp < time() {
                 println!("Proposal with ID {} is now closed for voting", proposal.id);
-                proposal.is_open = false;
-                ids_to_execute.push(proposal.id);
+                proposal.close();
+                if !proposal.will_execute() {
+                    println!("Proposal with ID {} did not pass", proposal.id);
+                }
             }
         }
     });
+    let mut ids_to_execute = proposals_due_for_execution();
+    // Failed executions whose backoff has elapsed are retried as well
+    ids_to_execute.extend(proposals_due_for_retry());
+    ids_to_execute.sort_unstable();
+    ids_to_execute.dedup();
 
     // Execute each proposal asynchronously
     for id in ids_to_execute {
         println!("Attempting to execute proposal with ID {}", id);
-        match execute_proposal(id).await {
-            Ok(summary) => println!("Executed proposal {}: {}", id, summary),
-            Err(e) => println!("Error executing proposal {}: {}", e, id),
+        match attempt_execution(id, false).await {
+            Ok(tx_hash) => println!("Executed proposal {}: {}", id, tx_hash),
+            Err(e) => println!("Error executing proposal {}: {}", id, e),
         }
     }
+
+    // Follow up on execution transactions that have not been mined yet
+    check_pending_transactions().await;
+
+    // Sign the results of newly closed proposals so that anyone can relay them
+    attest_closed_proposals().await;
+
+    refresh_stale_records().await;
 }
 
 
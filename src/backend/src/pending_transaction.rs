@@ -0,0 +1,185 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::api::time;
+use ic_cdk::println;
+
+use crate::action_batch::record_batch_receipt;
+use crate::config::{read_config, Config};
+use crate::declarations::evm_rpc::TransactionReceipt;
+use crate::eth_rpc::{send_transaction, transaction_receipt};
+use crate::execution_retry::record_transaction_receipt;
+use crate::transaction::{AccessListEntry, TransactionType};
+use crate::treasury::record_transfer_receipt;
+use crate::PROPOSALS;
+
+// Nodes only accept a replacement if both fee caps rise by at least 10%
+const FEE_BUMP_PERCENT: u64 = 15;
+// Upper bound for bumped fees so that a congested network cannot drain the canister's ETH
+const MAX_FEE_PER_GAS_CEILING: u128 = 1_000_000_000_000; // 1000 gwei
+
+/// An outgoing Ethereum transaction that has been broadcast but not yet seen in a block.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PendingTransaction {
+    pub to: String,
+    pub value: Nat,
+    /// Hex encoded calldata, `None` for plain transfers.
+    pub data: Option<String>,
+    pub tx_type: TransactionType,
+    pub access_list: Vec<AccessListEntry>,
+    pub nonce: Nat,
+    /// The gas price for legacy and access list transactions.
+    pub max_fee_per_gas: Nat,
+    pub max_priority_fee_per_gas: Nat,
+    /// Hash of the most recently broadcast version of this transaction.
+    pub hash: String,
+    /// Hashes of every broadcast version of this transaction, including `hash`. Only these can
+    /// settle it, unlike the hashes of earlier actions or attempts of the same proposal.
+    pub hashes: Vec<String>,
+    pub submitted_at: u64,
+    pub is_cancellation: bool,
+}
+
+impl PendingTransaction {
+    /// Returns the same transaction with both fee caps raised by `FEE_BUMP_PERCENT`.
+    pub fn bumped(&self) -> Result<Self, String> {
+        let max_fee_per_gas = bump_fee(&self.max_fee_per_gas);
+        let ceiling = Nat::from(MAX_FEE_PER_GAS_CEILING);
+        if max_fee_per_gas > ceiling {
+            return Err(format!(
+                "Bumped max fee per gas {} exceeds the ceiling of {}",
+                max_fee_per_gas, MAX_FEE_PER_GAS_CEILING
+            ));
+        }
+        Ok(Self {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: bump_fee(&self.max_priority_fee_per_gas),
+            ..self.clone()
+        })
+    }
+
+    /// Returns a zero-value self-send that reuses the nonce of this transaction, which
+    /// invalidates it once mined.
+    pub fn cancellation(&self, self_address: String) -> Result<Self, String> {
+        Ok(Self {
+            to: self_address,
+            value: 0_u8.into(),
+            data: None,
+            access_list: Vec::new(),
+            is_cancellation: true,
+            ..self.bumped()?
+        })
+    }
+}
+
+fn bump_fee(fee: &Nat) -> Nat {
+    fee.clone() * Nat::from(100 + FEE_BUMP_PERCENT) / Nat::from(100_u64) + Nat::from(1_u64)
+}
+
+/// Broadcasts `replacement` for the pending transaction of a proposal and records the new hash.
+pub async fn replace_pending_transaction(
+    proposal_id: u64,
+    mut replacement: PendingTransaction,
+) -> Result<String, String> {
+    let tx_hash = send_transaction(&replacement, Some(proposal_id)).await?;
+    replacement.hash = tx_hash.clone();
+    replacement.hashes.push(tx_hash.clone());
+    replacement.submitted_at = time();
+
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+            proposal.eth_transaction_hash = Some(tx_hash.clone());
+            proposal.eth_transaction_hashes.push(tx_hash.clone());
+            proposal.pending_transaction = Some(replacement);
+        }
+    });
+
+    Ok(tx_hash)
+}
+
+/// Returns the hash among `tx_hashes` that made it into a block and its receipt, if any.
+async fn find_mined_hash(
+    proposal_id: u64,
+    tx_hashes: &[String],
+) -> Result<Option<(String, TransactionReceipt)>, String> {
+    // Later replacements carry higher fees and are the most likely to be mined.
+    for tx_hash in tx_hashes.iter().rev() {
+        if let Some(receipt) = transaction_receipt(tx_hash, Some(proposal_id)).await? {
+            return Ok(Some((tx_hash.clone(), receipt)));
+        }
+    }
+    Ok(None)
+}
+
+/// Clears pending transactions that have been mined and replaces the ones that are stuck.
+pub async fn check_pending_transactions() {
+    let pending = PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .filter_map(|p| p.pending_transaction.clone().map(|tx| (p.id, tx)))
+            .collect::<Vec<_>>()
+    });
+
+    let stuck_threshold_nanos = read_config(Config::stuck_transaction_threshold);
+    for (proposal_id, tx) in pending {
+        match find_mined_hash(proposal_id, &tx.hashes).await {
+            Ok(Some((mined_hash, receipt))) => {
+                println!("Transaction {} of proposal {} was mined", mined_hash, proposal_id);
+                // Skip the receipt if another transaction was sent while it was fetched
+                let settled = PROPOSALS.with(|proposals| {
+                    let mut proposals = proposals.borrow_mut();
+                    let proposal = proposals.iter_mut().find(|p| p.id == proposal_id)?;
+                    if !proposal
+                        .pending_transaction
+                        .as_ref()
+                        .is_some_and(|pending| pending.hashes.contains(&mined_hash))
+                    {
+                        return None;
+                    }
+                    proposal.eth_transaction_hash = Some(mined_hash.clone());
+                    proposal.pending_transaction = None;
+                    Some(())
+                });
+                if settled.is_none() {
+                    continue;
+                }
+                // A mined cancellation is a self-send, unlike any of the replaced transactions
+                let cancelled = tx.is_cancellation && receipt.to.eq_ignore_ascii_case(&tx.to);
+                record_batch_receipt(proposal_id, &mined_hash, receipt.status == 1_u8, cancelled);
+                record_transaction_receipt(
+                    proposal_id,
+                    &mined_hash,
+                    receipt.status == 1_u8,
+                    cancelled,
+                );
+                record_transfer_receipt(
+                    proposal_id,
+                    &tx.hashes,
+                    &mined_hash,
+                    receipt.status == 1_u8,
+                    cancelled,
+                );
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                println!("Error checking transactions of proposal {}: {}", proposal_id, e);
+                continue;
+            }
+        }
+
+        if time().saturating_sub(tx.submitted_at) < stuck_threshold_nanos {
+            continue;
+        }
+
+        println!("Transaction {} of proposal {} is stuck, bumping fees", tx.hash, proposal_id);
+        let result = match tx.bumped() {
+            Ok(replacement) => replace_pending_transaction(proposal_id, replacement).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(tx_hash) => println!("Replaced transaction of proposal {}: {}", proposal_id, tx_hash),
+            Err(e) => println!("Error replacing transaction of proposal {}: {}", proposal_id, e),
+        }
+    }
+}
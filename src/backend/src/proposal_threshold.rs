@@ -0,0 +1,259 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::println;
+use std::future::Future;
+
+use crate::config::read_config;
+use crate::eth_rpc::try_eth_balance_of;
+use crate::service::save_my_profile::get_address_of;
+use crate::SPONSORSHIPS;
+
+/// Why a proposal was not accepted.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum SubmitError {
+    /// The submitter has no Ethereum address linked through SIWE and no sponsor.
+    NoLinkedAddress,
+    /// The voting power of the submitter at the snapshot block is below the proposal
+    /// threshold, and no sponsor backing them meets it either.
+    BelowThreshold {
+        address: String,
+        voting_power: Nat,
+        threshold: Nat,
+    },
+    /// The proposal is invalid or the caller may not submit it.
+    Rejected(String),
+}
+
+impl From<String> for SubmitError {
+    fn from(error: String) -> Self {
+        SubmitError::Rejected(error)
+    }
+}
+
+fn proposal_threshold() -> Option<Nat> {
+    read_config(|c| c.governance.proposal_threshold.clone())
+}
+
+// Voting power of an address at a block, using the same strategy as votes
+async fn voting_power(address: &str, block_height: &str) -> Result<Nat, SubmitError> {
+    try_eth_balance_of(address, block_height, None)
+        .await
+        .map_err(|e| format!("Failed to fetch the voting power of {address}: {e}").into())
+}
+
+/// Checks that `submitter` may open a proposal snapshotting `block_height`. Submitters below the
+/// threshold use up the sponsorship of the first sponsor who meets it, whose address is
+/// returned.
+pub async fn check_proposal_threshold(
+    submitter: Principal,
+    address: Option<&str>,
+    block_height: &str,
+) -> Result<Option<String>, SubmitError> {
+    let Some(threshold) = proposal_threshold() else {
+        return Ok(None);
+    };
+    let sponsor_address = check_threshold(
+        submitter,
+        address,
+        &threshold,
+        |address| async move {
+            let power = voting_power(&address, block_height).await;
+            if let Err(e) = &power {
+                println!("Skipping the voting power of {}: {:?}", address, e);
+            }
+            power
+        },
+        get_address_of,
+    )
+    .await?;
+    if let Some(sponsor_address) = &sponsor_address {
+        println!("{} sponsored a proposal of {}", sponsor_address, submitter);
+    }
+    Ok(sponsor_address)
+}
+
+// Compares the voting power from `power_of` with the threshold, falling back to the sponsors
+// of `submitter`, whose addresses come from `address_of`
+async fn check_threshold<P, PF, A, AF>(
+    submitter: Principal,
+    address: Option<&str>,
+    threshold: &Nat,
+    power_of: P,
+    address_of: A,
+) -> Result<Option<String>, SubmitError>
+where
+    P: Fn(String) -> PF,
+    PF: Future<Output = Result<Nat, SubmitError>>,
+    A: Fn(Principal) -> AF,
+    AF: Future<Output = Result<String, String>>,
+{
+    let power = match address {
+        Some(address) => power_of(address.to_string()).await?,
+        None => Nat::from(0_u8),
+    };
+    if power >= *threshold {
+        return Ok(None);
+    }
+
+    let sponsors = SPONSORSHIPS.with(|s| s.borrow().get(&submitter).cloned().unwrap_or_default());
+    for sponsor in sponsors {
+        let Ok(sponsor_address) = address_of(sponsor).await else {
+            continue;
+        };
+        // Sponsors whose voting power cannot be fetched are skipped like those below it
+        match power_of(sponsor_address.clone()).await {
+            Ok(power) if power >= *threshold => {}
+            _ => continue,
+        }
+        // A concurrent submission may have used the sponsorship during the awaits above
+        let used = SPONSORSHIPS.with(|s| {
+            let mut s = s.borrow_mut();
+            let Some(sponsors) = s.get_mut(&submitter) else {
+                return false;
+            };
+            let listed = sponsors.contains(&sponsor);
+            sponsors.retain(|p| *p != sponsor);
+            if sponsors.is_empty() {
+                s.remove(&submitter);
+            }
+            listed
+        });
+        if used {
+            return Ok(Some(sponsor_address));
+        }
+    }
+
+    match address {
+        None => Err(SubmitError::NoLinkedAddress),
+        Some(address) => Err(SubmitError::BelowThreshold {
+            address: address.to_string(),
+            voting_power: power,
+            threshold: threshold.clone(),
+        }),
+    }
+}
+
+/// Lets the caller back the next proposal of `proposer`. The caller must meet the threshold
+/// now and again when the proposal is submitted.
+pub async fn sponsor(sponsor: Principal, proposer: Principal) -> Result<(), SubmitError> {
+    if sponsor == proposer {
+        return Err("Proposers cannot sponsor themselves".to_string().into());
+    }
+    let threshold =
+        proposal_threshold().ok_or_else(|| "No proposal threshold is configured".to_string())?;
+    let address = get_address_of(sponsor)
+        .await
+        .map_err(|_| SubmitError::NoLinkedAddress)?;
+    let power = voting_power(&address, "latest").await?;
+    if power < threshold {
+        return Err(SubmitError::BelowThreshold {
+            address,
+            voting_power: power,
+            threshold,
+        });
+    }
+    SPONSORSHIPS.with(|s| {
+        let mut s = s.borrow_mut();
+        let sponsors = s.entry(proposer).or_default();
+        if sponsors.contains(&sponsor) {
+            return Err(format!("{sponsor} already sponsors {proposer}").into());
+        }
+        sponsors.push(sponsor);
+        Ok(())
+    })
+}
+
+/// Withdraws a sponsorship of the caller that has not been used yet.
+pub fn withdraw_sponsorship(sponsor: Principal, proposer: Principal) -> Result<(), String> {
+    SPONSORSHIPS.with(|s| {
+        let mut s = s.borrow_mut();
+        let sponsors = s
+            .get_mut(&proposer)
+            .filter(|sponsors| sponsors.contains(&sponsor))
+            .ok_or_else(|| format!("{sponsor} does not sponsor {proposer}"))?;
+        sponsors.retain(|p| *p != sponsor);
+        if sponsors.is_empty() {
+            s.remove(&proposer);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    const SUBMITTER_ADDRESS: &str = "0x0000000000000000000000000000000000000001";
+    const SPONSOR_ADDRESS: &str = "0x0000000000000000000000000000000000000002";
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn check(
+        submitter: Principal,
+        address: Option<&str>,
+        powers: &[(&str, u64)],
+    ) -> Result<Option<String>, SubmitError> {
+        let power_of = |address: String| {
+            let power = powers.iter().find(|(a, _)| *a == address).map(|(_, p)| *p);
+            async move { Ok(Nat::from(power.unwrap_or_default())) }
+        };
+        let address_of = |_: Principal| async { Ok(SPONSOR_ADDRESS.to_string()) };
+        block_on(check_threshold(
+            submitter,
+            address,
+            &Nat::from(100_u8),
+            power_of,
+            address_of,
+        ))
+    }
+
+    #[test]
+    fn accepts_submitters_meeting_the_threshold() {
+        let result = check(
+            principal(1),
+            Some(SUBMITTER_ADDRESS),
+            &[(SUBMITTER_ADDRESS, 100)],
+        );
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn rejects_submitters_below_the_threshold() {
+        let result = check(
+            principal(2),
+            Some(SUBMITTER_ADDRESS),
+            &[(SUBMITTER_ADDRESS, 99)],
+        );
+        assert!(matches!(
+            result,
+            Err(SubmitError::BelowThreshold { voting_power, .. }) if voting_power == 99_u8
+        ));
+        let result = check(principal(2), None, &[]);
+        assert!(matches!(result, Err(SubmitError::NoLinkedAddress)));
+    }
+
+    #[test]
+    fn uses_up_the_sponsorship_of_a_sponsor_meeting_the_threshold() {
+        let submitter = principal(3);
+        SPONSORSHIPS.with(|s| s.borrow_mut().insert(submitter, vec![principal(4)]));
+        let powers = [(SUBMITTER_ADDRESS, 0), (SPONSOR_ADDRESS, 100)];
+
+        let result = check(submitter, Some(SUBMITTER_ADDRESS), &powers);
+        assert!(matches!(result, Ok(Some(address)) if address == SPONSOR_ADDRESS));
+        let result = check(submitter, Some(SUBMITTER_ADDRESS), &powers);
+        assert!(matches!(result, Err(SubmitError::BelowThreshold { .. })));
+    }
+
+    #[test]
+    fn keeps_the_sponsorship_of_a_sponsor_below_the_threshold() {
+        let submitter = principal(5);
+        SPONSORSHIPS.with(|s| s.borrow_mut().insert(submitter, vec![principal(6)]));
+
+        let result = check(submitter, None, &[(SPONSOR_ADDRESS, 99)]);
+        assert!(matches!(result, Err(SubmitError::NoLinkedAddress)));
+        let sponsors = SPONSORSHIPS.with(|s| s.borrow().get(&submitter).cloned());
+        assert_eq!(sponsors, Some(vec![principal(6)]));
+    }
+}
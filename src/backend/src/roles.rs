@@ -0,0 +1,177 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::{is_controller, time};
+use ic_cdk::{caller, println};
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+
+use crate::config::read_config;
+use crate::{ROLES, ROLE_CHANGES};
+
+/// Permissions over the administration of the canister.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Holds every role and manages all of them. Controllers of the canister always hold it.
+    Controller,
+    /// Calls the admin endpoints and manages the proposer, executor and guardian roles.
+    Admin,
+    /// Submits proposals.
+    Proposer,
+    /// Executes passed proposals with `execute_proposal`.
+    Executor,
+    /// Vetoes queued proposals on their own, like the guardians of the config.
+    Guardian,
+}
+
+/// Roles granted to a principal.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct RoleSet(pub Vec<Role>);
+
+impl Storable for RoleSet {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// A grant or revocation of a role, kept as an audit log.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RoleChange {
+    pub timestamp: u64,
+    /// Principal that made the change.
+    pub changed_by: Principal,
+    pub principal: Principal,
+    pub role: Role,
+    pub granted: bool,
+}
+
+impl Storable for RoleChange {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+fn granted_roles(principal: &Principal) -> Vec<Role> {
+    ROLES
+        .with(|r| r.borrow().get(&principal.to_text()))
+        .unwrap_or_default()
+        .0
+}
+
+pub fn has_role(principal: &Principal, role: Role) -> bool {
+    if is_controller(principal) {
+        return true;
+    }
+    let granted = granted_roles(principal);
+    if granted.contains(&Role::Controller) || granted.contains(&role) {
+        return true;
+    }
+    role == Role::Guardian && read_config(|c| c.is_guardian(principal))
+}
+
+/// Whether any principal was granted `role`.
+fn is_assigned(role: Role) -> bool {
+    ROLES.with(|r| r.borrow().iter().any(|(_, roles)| roles.0.contains(&role)))
+}
+
+/// Fails unless the caller holds `role`. The proposer and executor roles only restrict their
+/// endpoints once they are granted to someone, so that a fresh canister stays open.
+pub fn require_role(role: Role) -> Result<(), String> {
+    let open = matches!(role, Role::Proposer | Role::Executor) && !is_assigned(role);
+    if open || has_role(&caller(), role) {
+        Ok(())
+    } else {
+        Err(format!("The caller does not have the {role:?} role"))
+    }
+}
+
+// Admins can hand out the operational roles, but only controllers can create other admins
+fn can_manage(manager: &Principal, role: Role) -> bool {
+    match role {
+        Role::Controller | Role::Admin => has_role(manager, Role::Controller),
+        Role::Proposer | Role::Executor | Role::Guardian => has_role(manager, Role::Admin),
+    }
+}
+
+/// Grants or revokes `role` for `principal` on behalf of the caller and logs the change.
+pub fn set_role(principal: Principal, role: Role, granted: bool) -> Result<(), String> {
+    let changed_by = caller();
+    if !can_manage(&changed_by, role) {
+        return Err(format!("The caller cannot manage the {role:?} role"));
+    }
+    let mut roles = granted_roles(&principal);
+    if roles.contains(&role) == granted {
+        return Err(format!(
+            "{principal} {} the {role:?} role",
+            if granted {
+                "already has"
+            } else {
+                "does not have"
+            }
+        ));
+    }
+    if granted {
+        roles.push(role);
+        roles.sort();
+    } else {
+        roles.retain(|r| *r != role);
+    }
+    ROLES.with(|r| {
+        let mut r = r.borrow_mut();
+        if roles.is_empty() {
+            r.remove(&principal.to_text());
+        } else {
+            r.insert(principal.to_text(), RoleSet(roles));
+        }
+    });
+
+    let change = RoleChange {
+        timestamp: time(),
+        changed_by,
+        principal,
+        role,
+        granted,
+    };
+    println!(
+        "{} {} the {:?} role {} {}",
+        changed_by,
+        if granted { "granted" } else { "revoked" },
+        role,
+        if granted { "to" } else { "from" },
+        principal
+    );
+    ROLE_CHANGES.with(|log| {
+        let mut log = log.borrow_mut();
+        let id = log.len();
+        log.insert(id, change);
+    });
+    Ok(())
+}
+
+/// Granted roles by principal. Controllers of the canister are not listed.
+pub fn role_assignments() -> Vec<(Principal, Vec<Role>)> {
+    ROLES.with(|r| {
+        r.borrow()
+            .iter()
+            .filter_map(|(principal, roles)| {
+                Principal::from_text(principal)
+                    .ok()
+                    .map(|principal| (principal, roles.0))
+            })
+            .collect()
+    })
+}
+
+pub fn role_changes() -> Vec<RoleChange> {
+    ROLE_CHANGES.with(|log| log.borrow().iter().map(|(_, change)| change).collect())
+}
@@ -0,0 +1,216 @@
+use candid::{CandidType, Deserialize, Nat};
+use ethers_core::abi::{encode, Contract, Token};
+use ethers_core::types::{Address, U256};
+use ethers_core::utils::keccak256;
+use std::str::FromStr;
+
+use crate::config::read_config;
+use crate::eth_rpc::{
+    eth_call, eth_transaction, nat_to_u256, parse_address, pubkey_and_signature, to_hex, y_parity,
+};
+use crate::execution_payload::ExecutionPayload;
+use crate::pending_transaction::PendingTransaction;
+use crate::signing_space::{derivation_path, space_of_proposal};
+use crate::PROPOSALS;
+
+const DOMAIN_SEPARATOR_TYPE: &str = "EIP712Domain(uint256 chainId,address verifyingContract)";
+const SAFE_TX_TYPE: &str = "SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)";
+// Safe operation for a plain call, as opposed to a delegate call
+const OPERATION_CALL: u8 = 0;
+
+/// How the canister's address is attached to the Safe.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafeRole {
+    /// An owner, which signs Safe transactions. Executing directly needs a threshold of 1.
+    Owner,
+    /// An enabled module, which executes without signatures.
+    Module,
+}
+
+/// A Gnosis Safe that executes proposal payloads on behalf of the canister.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SafeConfig {
+    pub address: String,
+    pub role: SafeRole,
+}
+
+/// What the canister does with a payload routed through the Safe.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafeMode {
+    /// Executes the Safe transaction right away.
+    Execute,
+    /// Signs the Safe transaction and approves its hash on-chain, leaving the execution to the
+    /// other owners once they reach the threshold. Only available to owners.
+    Propose,
+}
+
+/// A Safe transaction signed by the canister, kept so that cosigners can verify and execute it.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SignedSafeTransaction {
+    pub safe: String,
+    pub to: String,
+    pub value: Nat,
+    pub data: String,
+    pub nonce: Nat,
+    pub mode: SafeMode,
+    /// EIP-712 hash of the SafeTx.
+    pub safe_tx_hash: String,
+    /// The canister's 65-byte signature of `safe_tx_hash`.
+    pub signature: String,
+}
+
+fn safe_abi() -> Contract {
+    ethers_core::abi::parse_abi(&[
+        "function nonce() view returns (uint256)",
+        "function execTransaction(address to, uint256 value, bytes data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, bytes signatures) payable returns (bool)",
+        "function execTransactionFromModule(address to, uint256 value, bytes data, uint8 operation) returns (bool)",
+        "function approveHash(bytes32 hashToApprove)",
+    ])
+    .expect("Safe ABI is valid")
+}
+
+pub fn safe_config() -> Result<SafeConfig, String> {
+    read_config(|c| c.safe.clone()).ok_or_else(|| "No Safe is configured".to_string())
+}
+
+/// Checks that the configured Safe supports `mode`.
+pub fn validate_mode(mode: SafeMode) -> Result<(), String> {
+    let safe = safe_config()?;
+    parse_address(&safe.address)?;
+    if safe.role == SafeRole::Module && mode == SafeMode::Propose {
+        return Err("The canister is a Safe module and cannot propose transactions".to_string());
+    }
+    Ok(())
+}
+
+/// Performs the call of `payload` from the Safe instead of the canister's address.
+pub async fn execute_through_safe(
+    payload: &ExecutionPayload,
+    mode: SafeMode,
+    proposal_id: u64,
+) -> Result<PendingTransaction, String> {
+    let safe = safe_config()?;
+    let abi = safe_abi();
+    let to = Token::Address(parse_address(&payload.target)?);
+    let value = Token::Uint(nat_to_u256(&payload.value));
+    let data = payload.calldata()?;
+    let operation = Token::Uint(OPERATION_CALL.into());
+
+    if safe.role == SafeRole::Module {
+        let args = [to, value, Token::Bytes(data), operation];
+        return eth_transaction(
+            safe.address,
+            &abi,
+            "execTransactionFromModule",
+            &args,
+            Some(proposal_id),
+        )
+        .await;
+    }
+
+    let nonce = match eth_call(
+        safe.address.clone(),
+        &abi,
+        "nonce",
+        &[],
+        "latest",
+        Some(proposal_id),
+    )
+    .await
+    .first()
+    {
+        Some(Token::Uint(nonce)) => *nonce,
+        other => return Err(format!("Unexpected Safe nonce: {other:?}")),
+    };
+    let safe_tx_hash = safe_tx_hash(&safe.address, payload, &data, nonce)?;
+    let signature = sign_hash(&safe_tx_hash, proposal_id).await;
+    let signed = SignedSafeTransaction {
+        safe: safe.address.clone(),
+        to: payload.target.clone(),
+        value: payload.value.clone(),
+        data: to_hex(&data),
+        nonce: Nat::from_str(&nonce.to_string()).map_err(|e| e.to_string())?,
+        mode,
+        safe_tx_hash: to_hex(&safe_tx_hash),
+        signature: to_hex(&signature),
+    };
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        if let Some(proposal) = proposals.iter_mut().find(|p| p.id == proposal_id) {
+            proposal.safe_transactions.push(signed);
+        }
+    });
+
+    match mode {
+        SafeMode::Execute => {
+            let zero = Token::Uint(U256::zero());
+            let no_address = Token::Address(Address::zero());
+            let args = [
+                to,
+                value,
+                Token::Bytes(data),
+                operation,
+                zero.clone(),
+                zero.clone(),
+                zero,
+                no_address.clone(),
+                no_address,
+                Token::Bytes(signature),
+            ];
+            eth_transaction(
+                safe.address,
+                &abi,
+                "execTransaction",
+                &args,
+                Some(proposal_id),
+            )
+            .await
+        }
+        SafeMode::Propose => {
+            let args = [Token::FixedBytes(safe_tx_hash.to_vec())];
+            eth_transaction(safe.address, &abi, "approveHash", &args, Some(proposal_id)).await
+        }
+    }
+}
+
+// EIP-712 hash of a SafeTx without gas refunds, as signed by the owners
+fn safe_tx_hash(
+    safe: &str,
+    payload: &ExecutionPayload,
+    data: &[u8],
+    nonce: U256,
+) -> Result<[u8; 32], String> {
+    let domain_separator = keccak256(encode(&[
+        Token::FixedBytes(keccak256(DOMAIN_SEPARATOR_TYPE).to_vec()),
+        Token::Uint(read_config(|c| c.chain_id).into()),
+        Token::Address(parse_address(safe)?),
+    ]));
+    let zero = Token::Uint(U256::zero());
+    let no_address = Token::Address(Address::zero());
+    let struct_hash = keccak256(encode(&[
+        Token::FixedBytes(keccak256(SAFE_TX_TYPE).to_vec()),
+        Token::Address(parse_address(&payload.target)?),
+        Token::Uint(nat_to_u256(&payload.value)),
+        Token::FixedBytes(keccak256(data).to_vec()),
+        Token::Uint(OPERATION_CALL.into()),
+        zero.clone(),
+        zero.clone(),
+        zero,
+        no_address.clone(),
+        no_address,
+        Token::Uint(nonce),
+    ]));
+    Ok(keccak256(
+        [&[0x19, 0x01], &domain_separator[..], &struct_hash[..]].concat(),
+    ))
+}
+
+// Signs a digest with the key of the proposal's signing space, in the `r || s || v` form the
+// Safe expects
+async fn sign_hash(hash: &[u8; 32], proposal_id: u64) -> Vec<u8> {
+    let path = derivation_path(space_of_proposal(Some(proposal_id)).as_deref());
+    let (pubkey, mut signature) = pubkey_and_signature(hash.to_vec(), path).await;
+    let v = 27 + y_parity(hash, &signature, &pubkey) as u8;
+    signature.push(v);
+    signature
+}
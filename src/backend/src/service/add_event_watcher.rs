@@ -0,0 +1,19 @@
+use ic_cdk::update;
+
+use crate::roles::{require_role, Role};
+use crate::{event_ingestion::EventWatcher, EVENT_WATCHERS};
+
+/// Starts turning events of a registered contract into proposals, replacing any watcher with
+/// the same name. Only admins may call this.
+#[update]
+fn add_event_watcher(watcher: EventWatcher) -> Result<(), String> {
+    require_role(Role::Admin)?;
+    if watcher.name.is_empty() {
+        return Err("Watcher name must not be empty".to_string());
+    }
+    // Fail early if the contract or event cannot be resolved
+    watcher.resolve_event()?;
+
+    EVENT_WATCHERS.with(|w| w.borrow_mut().insert(watcher.name.clone(), watcher));
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use ic_cdk::update;
+
+use crate::{
+    eth_rpc::get_space_eth_address,
+    pending_transaction::replace_pending_transaction,
+    roles::{require_role, Role},
+    signing_space::space_of_proposal,
+    PROPOSALS,
+};
+
+/// Cancels the pending execution transaction of a proposal by replacing it with a zero-value
+/// self-send that uses the same nonce and higher fees. Only admins may call this.
+#[update]
+async fn cancel_proposal_transaction(proposal_id: u64) -> Result<String, String> {
+    require_role(Role::Admin)?;
+
+    let pending = PROPOSALS
+        .with(|proposals| {
+            proposals
+                .borrow()
+                .iter()
+                .find(|p| p.id == proposal_id)
+                .map(|p| p.pending_transaction.clone())
+        })
+        .ok_or_else(|| format!("Proposal {proposal_id} not found."))?
+        .ok_or_else(|| format!("Proposal {proposal_id} has no pending transaction."))?;
+
+    let self_address = get_space_eth_address(space_of_proposal(Some(proposal_id)).as_deref()).await;
+    let cancellation = pending.cancellation(self_address)?;
+    replace_pending_transaction(proposal_id, cancellation).await
+}
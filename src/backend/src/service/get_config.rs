@@ -0,0 +1,9 @@
+use ic_cdk::query;
+
+use crate::config::{read_config, Config};
+
+/// Returns the runtime configuration set by the init and upgrade arguments.
+#[query]
+fn get_config() -> Config {
+    read_config(Config::clone)
+}
@@ -0,0 +1,9 @@
+use ic_cdk::query;
+
+use crate::cycles::{cycles_report, CyclesReport};
+
+/// Returns the cycles spent on EVM RPC calls, broken down by operation and by proposal.
+#[query]
+fn get_cycles_usage() -> CyclesReport {
+    cycles_report()
+}
@@ -0,0 +1,10 @@
+use ic_cdk::{caller, update};
+
+use crate::wallet_link::issue_link_message;
+
+/// Issues a message for the caller to sign with `personal_sign` in the wallet it wants to link.
+/// The message expires after five minutes.
+#[update]
+async fn get_link_message() -> Result<String, String> {
+    issue_link_message(caller()).await
+}
@@ -7,6 +7,7 @@ use crate::{user_profile::UserProfile, USER_PROFILES};
 fn get_my_profile() -> Result<UserProfile, String> {
     USER_PROFILES
         .with(|p| p.borrow().get(&ic_cdk::caller().to_string()))
+        .map(UserProfile::with_ens)
         .map_or(
             Err("No profile found for the given address".to_string()),
             Ok,
@@ -0,0 +1,16 @@
+use ic_cdk::query;
+
+use crate::ens::cached_address_of;
+use crate::user_profile::{profile_by_address, UserProfile};
+
+/// Returns the principal that claimed an Ethereum address and its profile. The address is
+/// matched case-insensitively. ENS names are looked up among the verified names of profiles.
+#[query]
+fn get_profile_by_address(address: String) -> Result<(String, UserProfile), String> {
+    let resolved = match address.contains('.') {
+        true => cached_address_of(&address)
+            .ok_or_else(|| format!("No profile has the ENS name {address}"))?,
+        false => address.clone(),
+    };
+    profile_by_address(&resolved).ok_or_else(|| format!("No profile claims address {address}"))
+}
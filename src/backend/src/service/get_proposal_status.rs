@@ -0,0 +1,16 @@
+use ic_cdk::query;
+
+use crate::{timelock::ProposalSchedule, PROPOSALS};
+
+/// Returns the status of a proposal together with its execution ETA and counter-votes.
+#[query]
+fn get_proposal_status(proposal_id: u64) -> Result<ProposalSchedule, String> {
+    PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .find(|p| p.id == proposal_id)
+            .map(|p| p.schedule())
+            .ok_or_else(|| format!("Proposal {proposal_id} not found."))
+    })
+}
@@ -0,0 +1,11 @@
+use ic_cdk::query;
+
+use crate::attestation::{attestation, ResultAttestation};
+
+/// Returns the signed result of a closed proposal, which anyone can submit to the
+/// `ResultVerifier` contract.
+#[query]
+fn get_result_attestation(proposal_id: u64) -> Result<ResultAttestation, String> {
+    attestation(proposal_id)
+        .ok_or_else(|| format!("Proposal {proposal_id} has no result attestation"))
+}
@@ -0,0 +1,10 @@
+use ic_cdk::update;
+
+use crate::{eth_rpc::get_space_eth_address, signing_space::find_space};
+
+/// Returns the Ethereum address the proposals of a signing space send their transactions from.
+#[update]
+async fn get_signing_space_address(space: String) -> Result<String, String> {
+    let space = find_space(&space)?;
+    Ok(get_space_eth_address(Some(&space.name)).await)
+}
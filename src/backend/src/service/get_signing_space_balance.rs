@@ -0,0 +1,17 @@
+use ic_cdk::update;
+
+use crate::{
+    eth_rpc::{eth_balance_of, get_space_eth_address},
+    roles::{require_role, Role},
+    signing_space::find_space,
+};
+
+/// Returns the ETH balance in wei of the address of a signing space. The balance is a paid
+/// outcall, so only admins may call this.
+#[update]
+async fn get_signing_space_balance(space: String) -> Result<String, String> {
+    require_role(Role::Admin)?;
+    let space = find_space(&space)?;
+    let address = get_space_eth_address(Some(&space.name)).await;
+    Ok(eth_balance_of(&address, "latest", None).await.to_string())
+}
@@ -0,0 +1,12 @@
+use ic_cdk::update;
+
+use crate::roles::{require_role, Role};
+use crate::treasury::{treasury_balances, TreasuryBalance};
+
+/// Returns the ETH and token balances of the treasury's address. Every balance is a paid
+/// outcall, so only admins may call this.
+#[update]
+async fn get_treasury_balances() -> Result<Vec<TreasuryBalance>, String> {
+    require_role(Role::Admin)?;
+    treasury_balances().await
+}
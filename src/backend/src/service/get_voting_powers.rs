@@ -0,0 +1,56 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::{caller, update};
+
+use crate::roles::{has_role, Role};
+use crate::{eth_rpc::eth_balances_of, user_profile::display_name_of_address, PROPOSALS, VOTES};
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VoterPower {
+    pub address: String,
+    pub vote: bool,
+    pub voting_power: Nat,
+    /// Profile name of the principal that claimed the address.
+    pub name: Option<String>,
+}
+
+/// Looks up the voting power of every voter of a proposal at its snapshot block. The balances
+/// are fetched in JSON-RPC batches, so this costs a handful of outcalls regardless of turnout.
+/// Only the submitter and admins may call this.
+#[update]
+async fn get_voting_powers(proposal_id: u64) -> Result<Vec<VoterPower>, String> {
+    let (block_height, submitter) = PROPOSALS
+        .with(|proposals| {
+            proposals
+                .borrow()
+                .iter()
+                .find(|p| p.id == proposal_id)
+                .map(|p| (p.block_height.clone(), p.submitter.clone()))
+        })
+        .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
+    if caller().to_text() != submitter && !has_role(&caller(), Role::Admin) {
+        return Err("Only the submitter or an admin can look up voting powers".to_string());
+    }
+    let votes = VOTES.with(|votes| {
+        votes
+            .borrow()
+            .get(&proposal_id)
+            .map(|v| v.iter().map(|(voter, vote)| (voter.clone(), *vote)).collect::<Vec<_>>())
+            .unwrap_or_default()
+    });
+
+    let voters = votes.iter().map(|(voter, _)| voter.clone()).collect::<Vec<_>>();
+    let balances = eth_balances_of(&voters, &block_height, Some(proposal_id)).await?;
+
+    votes
+        .into_iter()
+        .zip(balances)
+        .map(|((address, vote), (_, balance))| {
+            Ok(VoterPower {
+                voting_power: balance.map_err(|e| format!("{address}: {e}"))?,
+                name: display_name_of_address(&address),
+                address,
+                vote,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,11 @@
+use candid::Principal;
+use ic_cdk::update;
+
+use crate::roles::{set_role, Role};
+
+/// Grants `role` to `principal`. Controllers manage every role, admins the proposer, executor
+/// and guardian roles.
+#[update]
+fn grant_role(principal: Principal, role: Role) -> Result<(), String> {
+    set_role(principal, role, true)
+}
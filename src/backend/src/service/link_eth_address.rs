@@ -0,0 +1,11 @@
+use ic_cdk::{caller, update};
+
+use crate::wallet_link::link_address;
+
+/// Links the address that signed the caller's pending link message to the caller, in place of an
+/// address from the SIWE provider. Contract wallets such as Safes pass their address as `wallet`
+/// and are verified through EIP-1271. Returns the linked address.
+#[update]
+async fn link_eth_address(signature: String, wallet: Option<String>) -> Result<String, String> {
+    link_address(caller(), &signature, wallet).await
+}
@@ -0,0 +1,8 @@
+use ic_cdk::query;
+
+use crate::{contract_registry::RegisteredContract, CONTRACTS};
+
+#[query]
+fn list_contracts() -> Vec<RegisteredContract> {
+    CONTRACTS.with(|c| c.borrow().iter().map(|(_, contract)| contract).collect())
+}
@@ -0,0 +1,8 @@
+use ic_cdk::query;
+
+use crate::{event_ingestion::EventWatcher, EVENT_WATCHERS};
+
+#[query]
+fn list_event_watchers() -> Vec<EventWatcher> {
+    EVENT_WATCHERS.with(|w| w.borrow().iter().map(|(_, watcher)| watcher).collect())
+}
@@ -4,6 +4,11 @@ use crate::{user_profile::UserProfile, USER_PROFILES};
 
 #[query]
 fn list_profiles() -> Result<Vec<(String, UserProfile)>, String> {
-    let profiles = USER_PROFILES.with(|p| p.borrow().iter().collect::<Vec<_>>());
+    let profiles = USER_PROFILES.with(|p| {
+        p.borrow()
+            .iter()
+            .map(|(principal, profile)| (principal, profile.with_ens()))
+            .collect::<Vec<_>>()
+    });
     Ok(profiles)
 }
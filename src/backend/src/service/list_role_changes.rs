@@ -0,0 +1,9 @@
+use ic_cdk::query;
+
+use crate::roles::{role_changes, RoleChange};
+
+/// Returns every grant and revocation of a role, oldest first.
+#[query]
+fn list_role_changes() -> Vec<RoleChange> {
+    role_changes()
+}
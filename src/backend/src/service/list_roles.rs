@@ -0,0 +1,11 @@
+use candid::Principal;
+use ic_cdk::query;
+
+use crate::roles::{role_assignments, Role};
+
+/// Returns the roles granted to each principal. Controllers of the canister hold every role
+/// without being listed.
+#[query]
+fn list_roles() -> Vec<(Principal, Vec<Role>)> {
+    role_assignments()
+}
@@ -0,0 +1,9 @@
+use ic_cdk::query;
+
+use crate::treasury::{transfers, TransferRecord};
+
+/// Lists outgoing treasury transfers, optionally only those of one proposal.
+#[query]
+fn list_treasury_transfers(proposal_id: Option<u64>) -> Vec<TransferRecord> {
+    transfers(proposal_id)
+}
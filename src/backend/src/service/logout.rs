@@ -0,0 +1,9 @@
+use ic_cdk::{caller, update};
+
+use crate::address_cache::invalidate_address;
+
+/// Forgets the cached address of the caller. Called by the frontend when the SIWE session ends.
+#[update]
+fn logout() {
+    invalidate_address(&caller());
+}
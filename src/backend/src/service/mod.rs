@@ -1,3 +1,37 @@
+pub mod add_event_watcher;
+pub mod cancel_proposal_transaction;
+pub mod get_config;
+pub mod get_cycles_usage;
+pub mod get_link_message;
 pub mod get_my_profile;
+pub mod get_profile_by_address;
+pub mod get_proposal_status;
+pub mod get_result_attestation;
+pub mod get_signing_space_address;
+pub mod get_signing_space_balance;
+pub mod get_treasury_balances;
+pub mod get_voting_powers;
+pub mod grant_role;
+pub mod link_eth_address;
+pub mod list_contracts;
+pub mod list_event_watchers;
 pub mod list_profiles;
+pub mod list_role_changes;
+pub mod list_roles;
+pub mod list_treasury_transfers;
+pub mod logout;
+pub mod register_contract;
+pub mod remove_event_watcher;
+pub mod retry_proposal_execution;
+pub mod revoke_role;
 pub mod save_my_profile;
+pub mod simulate_proposal;
+pub mod sponsor_proposer;
+pub mod start_session;
+pub mod submit_batch_proposal;
+pub mod submit_canister_call_proposal;
+pub mod submit_treasury_proposal;
+pub mod unlink_eth_address;
+pub mod unregister_contract;
+pub mod veto_proposal;
+pub mod withdraw_sponsorship;
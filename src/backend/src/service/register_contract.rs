@@ -0,0 +1,25 @@
+use ic_cdk::update;
+
+use crate::contract_registry::{insert_contract, RegisteredContract};
+use crate::roles::{require_role, Role};
+
+/// Registers a contract under `name` so that proposals can target it without a redeploy. An
+/// existing registration with the same name is replaced. Only admins may call this.
+#[update]
+fn register_contract(
+    name: String,
+    address: String,
+    chain_id: u64,
+    abi: String,
+) -> Result<RegisteredContract, String> {
+    require_role(Role::Admin)?;
+
+    let contract = RegisteredContract {
+        name,
+        address,
+        chain_id,
+        abi,
+    };
+    insert_contract(contract.clone())?;
+    Ok(contract)
+}
@@ -0,0 +1,15 @@
+use ic_cdk::update;
+
+use crate::roles::{require_role, Role};
+use crate::EVENT_WATCHERS;
+
+/// Stops ingesting the events of a watcher. Only admins may call this.
+#[update]
+fn remove_event_watcher(name: String) -> Result<(), String> {
+    require_role(Role::Admin)?;
+
+    EVENT_WATCHERS
+        .with(|w| w.borrow_mut().remove(&name))
+        .map(|_| ())
+        .ok_or_else(|| format!("Event watcher {name} does not exist"))
+}
@@ -0,0 +1,13 @@
+use ic_cdk::update;
+
+use crate::attempt_execution;
+use crate::roles::{require_role, Role};
+
+/// Retries the execution of a proposal right away, also after its automatic attempts are
+/// used up. Only admins may call this.
+#[update]
+async fn retry_proposal_execution(proposal_id: u64) -> Result<String, String> {
+    require_role(Role::Admin)?;
+
+    attempt_execution(proposal_id, true).await
+}
@@ -0,0 +1,11 @@
+use candid::Principal;
+use ic_cdk::update;
+
+use crate::roles::{set_role, Role};
+
+/// Revokes `role` from `principal`, with the same permissions as `grant_role`. Controllers of
+/// the canister keep all roles regardless.
+#[update]
+fn revoke_role(principal: Principal, role: Role) -> Result<(), String> {
+    set_role(principal, role, false)
+}
@@ -1,39 +1,63 @@
+use candid::Principal;
 use ic_cdk::update;
 use serde_bytes::ByteBuf;
 
 use crate::{
-    declarations::ic_siwe_provider::{ic_siwe_provider, GetAddressResponse},
-    user_profile::UserProfile,
-    USER_PROFILES,
+    address_cache::{cached_address, update_cached_address},
+    config::{read_config, Config},
+    declarations::ic_siwe_provider::{GetAddressResponse, IcSiweProvider},
+    user_profile::{save_profile, UserProfile},
+    wallet_link::linked_address,
 };
 
 #[update]
 async fn save_my_profile(name: String, avatar_url: String) -> Result<UserProfile, String> {
     // Get the address of the caller from the siwe provider canister, return error if it fails. A failure
     // here means that the caller is not authenticated using the siwe provider. This might happen if the
-    // caller uses an anonymous principal or has authenticated using a different identity provider.
-    let address = get_address().await?;
+    // caller uses an anonymous principal or has authenticated using a different identity provider
+    // without linking an address through `link_eth_address`.
+    // The cache is bypassed here, so that a re-linked address replaces the cached one.
+    let address = fetch_address_of(ic_cdk::caller()).await?;
+    update_cached_address(&ic_cdk::caller(), address.clone());
 
     // If user has an address and thus is authenticated, create a profile and save it.
     let profile = UserProfile {
         address,
         name,
         avatar_url,
+        ens_name: None,
+        ens_avatar: None,
     };
 
-    USER_PROFILES.with(|p| {
-        let mut profiles = p.borrow_mut();
-        profiles.insert(ic_cdk::caller().to_string(), profile.clone());
-    });
+    // The ENS name is resolved by the timer, which bounds the outcalls spent on lookups
+    save_profile(ic_cdk::caller().to_string(), profile.clone());
 
-    Ok(profile)
+    Ok(profile.with_ens())
 }
 
 /// Call the `get_address` method on the siwe provider canister with the calling principal as an argument to get the
 /// address of the caller.
 pub async fn get_address() -> Result<String, String> {
-    let response = ic_siwe_provider
-        .get_address(ByteBuf::from(ic_cdk::caller().as_slice()))
+    get_address_of(ic_cdk::caller()).await
+}
+
+/// Gets the address linked to `principal`, from the cache if its current session was started with
+/// `start_session` and from the siwe provider canister otherwise.
+pub async fn get_address_of(principal: Principal) -> Result<String, String> {
+    if let Some(address) = cached_address(&principal) {
+        return Ok(address);
+    }
+    fetch_address_of(principal).await
+}
+
+/// Gets the address `principal` linked by signing a message, or else the one from the siwe provider
+/// canister.
+pub async fn fetch_address_of(principal: Principal) -> Result<String, String> {
+    if let Some(address) = linked_address(&principal) {
+        return Ok(address);
+    }
+    let response = IcSiweProvider(read_config(Config::siwe_provider_canister_id))
+        .get_address(ByteBuf::from(principal.as_slice()))
         .await;
 
     let address = match response {
@@ -0,0 +1,11 @@
+use ic_cdk::{caller, update};
+
+use crate::simulation::{simulate_proposal as simulate, SimulationResult};
+
+/// Runs the transactions a proposal would send on execution with `eth_call` and returns their
+/// outcomes, so that proposers can catch reverts before voting ends. An update call since the
+/// simulation goes through the EVM RPC canister. Only the submitter and admins may call this.
+#[update]
+async fn simulate_proposal(proposal_id: u64) -> Result<Vec<SimulationResult>, String> {
+    simulate(caller(), proposal_id).await
+}
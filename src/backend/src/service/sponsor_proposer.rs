@@ -0,0 +1,12 @@
+use candid::Principal;
+use ic_cdk::{caller, update};
+
+use crate::proposal_threshold::{sponsor, SubmitError};
+
+/// Backs the next proposal of `proposer`, who may then submit it without meeting the proposal
+/// threshold. The caller's voting power must meet the threshold both now and at the snapshot
+/// block of the proposal.
+#[update]
+async fn sponsor_proposer(proposer: Principal) -> Result<(), SubmitError> {
+    sponsor(caller(), proposer).await
+}
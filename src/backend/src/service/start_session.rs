@@ -0,0 +1,18 @@
+use ic_cdk::api::time;
+use ic_cdk::{caller, update};
+
+use crate::address_cache::cache_session_address;
+use crate::service::save_my_profile::fetch_address_of;
+
+/// Caches the caller's address until `expiration`, the expiration of its SIWE delegation in
+/// nanoseconds since the epoch, so that later calls skip the siwe provider. Called by the
+/// frontend once the caller is logged in.
+#[update]
+async fn start_session(expiration: u64) -> Result<String, String> {
+    if expiration <= time() {
+        return Err("The session has already expired".to_string());
+    }
+    let address = fetch_address_of(caller()).await?;
+    cache_session_address(caller(), address.clone(), expiration);
+    Ok(address)
+}
@@ -0,0 +1,23 @@
+use ic_cdk::update;
+
+use crate::proposal_threshold::SubmitError;
+use crate::roles::{require_role, Role};
+use crate::{action_batch::ActionBatch, new_caller_proposal, push_proposal};
+
+/// Submits a proposal that performs the contract calls of `batch` in order if it passes.
+#[update]
+async fn submit_batch_proposal(
+    title: String,
+    description: String,
+    proposal_type: String,
+    duration_seconds: u64,
+    batch: ActionBatch,
+) -> Result<u64, SubmitError> {
+    require_role(Role::Proposer)?;
+    let batch = batch.resolve_ens_targets().await?.validate()?;
+    let mut proposal =
+        new_caller_proposal(title, description, proposal_type, duration_seconds).await?;
+    proposal.action_results = batch.initial_results();
+    proposal.action_batch = Some(batch);
+    Ok(push_proposal(proposal))
+}
@@ -0,0 +1,22 @@
+use ic_cdk::update;
+
+use crate::proposal_threshold::SubmitError;
+use crate::roles::{require_role, Role};
+use crate::{canister_call::CanisterCall, new_caller_proposal, push_proposal};
+
+/// Submits a proposal that calls `call.method` on an allowlisted canister if it passes.
+#[update]
+async fn submit_canister_call_proposal(
+    title: String,
+    description: String,
+    proposal_type: String,
+    duration_seconds: u64,
+    call: CanisterCall,
+) -> Result<u64, SubmitError> {
+    require_role(Role::Proposer)?;
+    call.validate()?;
+    let mut proposal =
+        new_caller_proposal(title, description, proposal_type, duration_seconds).await?;
+    proposal.canister_call = Some(call);
+    Ok(push_proposal(proposal))
+}
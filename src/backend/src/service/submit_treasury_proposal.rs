@@ -0,0 +1,33 @@
+use ic_cdk::update;
+
+use crate::{
+    ens::resolve_address,
+    new_caller_proposal,
+    proposal_threshold::SubmitError,
+    push_proposal,
+    roles::{require_role, Role},
+    treasury::{TreasuryTransfer, TREASURY_PROPOSAL_TYPE},
+};
+
+/// Submits a proposal that sends ETH or a configured token from the canister's address to
+/// `transfer.recipient` if it passes.
+#[update]
+async fn submit_treasury_proposal(
+    title: String,
+    description: String,
+    duration_seconds: u64,
+    mut transfer: TreasuryTransfer,
+) -> Result<u64, SubmitError> {
+    require_role(Role::Proposer)?;
+    transfer.recipient = resolve_address(&transfer.recipient).await?;
+    transfer.validate()?;
+    let mut proposal = new_caller_proposal(
+        title,
+        description,
+        TREASURY_PROPOSAL_TYPE.to_string(),
+        duration_seconds,
+    )
+    .await?;
+    proposal.treasury_transfer = Some(transfer);
+    Ok(push_proposal(proposal))
+}
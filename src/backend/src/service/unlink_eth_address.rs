@@ -0,0 +1,9 @@
+use ic_cdk::{caller, update};
+
+use crate::wallet_link::unlink_address;
+
+/// Removes the address the caller linked with `link_eth_address` and returns it.
+#[update]
+fn unlink_eth_address() -> Result<String, String> {
+    unlink_address(&caller())
+}
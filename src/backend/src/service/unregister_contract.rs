@@ -0,0 +1,15 @@
+use ic_cdk::update;
+
+use crate::roles::{require_role, Role};
+use crate::CONTRACTS;
+
+/// Removes a contract from the registry. Only admins may call this.
+#[update]
+fn unregister_contract(name: String) -> Result<(), String> {
+    require_role(Role::Admin)?;
+
+    CONTRACTS
+        .with(|c| c.borrow_mut().remove(&name))
+        .map(|_| ())
+        .ok_or_else(|| format!("Contract {name} is not registered"))
+}
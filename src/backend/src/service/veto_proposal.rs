@@ -0,0 +1,66 @@
+use ic_cdk::{caller, println, update};
+
+use crate::{
+    roles::{has_role, Role},
+    config::read_config, eth_rpc::eth_balance_of, service::save_my_profile::get_address,
+    timelock::ProposalSchedule, PROPOSALS, VETOES,
+};
+
+/// Vetoes a proposal while it is queued behind its timelock. Guardians veto on their own,
+/// other callers add the voting power of their address to the counter-votes.
+#[update]
+async fn veto_proposal(proposal_id: u64) -> Result<ProposalSchedule, String> {
+    let is_guardian = has_role(&caller(), Role::Guardian);
+    let (block_height, guardian_veto) = PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        let proposal = proposals
+            .iter_mut()
+            .find(|p| p.id == proposal_id)
+            .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
+        if !proposal.in_veto_window() {
+            return Err(format!("Proposal {proposal_id} is not queued behind a timelock"));
+        }
+        if is_guardian {
+            println!("Guardian {} vetoed proposal {}", caller(), proposal_id);
+            proposal.is_vetoed = true;
+        }
+        Ok((proposal.block_height.clone(), is_guardian.then(|| proposal.schedule())))
+    })?;
+    if let Some(schedule) = guardian_veto {
+        return Ok(schedule);
+    }
+
+    let threshold = read_config(|c| c.governance.veto_threshold.clone())
+        .ok_or("Only guardians can veto proposals")?;
+    let voter = get_address().await?;
+    let first_veto = VETOES.with(|vetoes| {
+        vetoes
+            .borrow_mut()
+            .entry(proposal_id)
+            .or_default()
+            .insert(voter.clone())
+    });
+    if !first_veto {
+        return Err("You have already vetoed this proposal".to_string());
+    }
+
+    let voting_power = eth_balance_of(&voter, &block_height, Some(proposal_id)).await;
+
+    PROPOSALS.with(|proposals| {
+        let mut proposals = proposals.borrow_mut();
+        let proposal = proposals
+            .iter_mut()
+            .find(|p| p.id == proposal_id)
+            .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
+        // The timelock may have expired while the balance was fetched
+        if !proposal.in_veto_window() {
+            return Err(format!("Proposal {proposal_id} is no longer queued behind a timelock"));
+        }
+        proposal.veto_votes += voting_power;
+        if proposal.veto_votes >= threshold {
+            println!("Counter-votes vetoed proposal {}", proposal_id);
+            proposal.is_vetoed = true;
+        }
+        Ok(proposal.schedule())
+    })
+}
@@ -0,0 +1,10 @@
+use candid::Principal;
+use ic_cdk::{caller, update};
+
+use crate::proposal_threshold::withdraw_sponsorship as withdraw;
+
+/// Withdraws the caller's sponsorship of `proposer` if it has not been used yet.
+#[update]
+fn withdraw_sponsorship(proposer: Principal) -> Result<(), String> {
+    withdraw(caller(), proposer)
+}
@@ -0,0 +1,54 @@
+use candid::{CandidType, Deserialize};
+
+use crate::config::read_config;
+use crate::PROPOSALS;
+
+/// A group of proposal types that signs from its own derived Ethereum address, with separate
+/// nonces and funds. Proposal types outside of all spaces use the canister's root address.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SigningSpace {
+    /// Also the derivation path of the space's key, so renaming a space changes its address.
+    pub name: String,
+    pub proposal_types: Vec<String>,
+}
+
+/// The space a proposal type signs in, `None` for the root address.
+pub fn space_of_type(proposal_type: &str) -> Option<String> {
+    read_config(|c| {
+        c.signing_spaces
+            .iter()
+            .flatten()
+            .find(|space| space.proposal_types.iter().any(|t| t == proposal_type))
+            .map(|space| space.name.clone())
+    })
+}
+
+/// The space the transactions of a proposal are signed in, as resolved when it was submitted.
+/// Calls that do not belong to a proposal use the root address.
+pub fn space_of_proposal(proposal_id: Option<u64>) -> Option<String> {
+    PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .find(|p| Some(p.id) == proposal_id)
+            .and_then(|p| p.signing_space.clone())
+    })
+}
+
+pub fn find_space(name: &str) -> Result<SigningSpace, String> {
+    read_config(|c| {
+        c.signing_spaces
+            .iter()
+            .flatten()
+            .find(|space| space.name == name)
+            .cloned()
+    })
+    .ok_or_else(|| format!("Signing space {name} does not exist"))
+}
+
+/// ECDSA derivation path of the key of `space`, empty for the root key.
+pub fn derivation_path(space: Option<&str>) -> Vec<Vec<u8>> {
+    space
+        .map(|name| vec![name.as_bytes().to_vec()])
+        .unwrap_or_default()
+}
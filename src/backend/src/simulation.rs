@@ -0,0 +1,268 @@
+use candid::{CandidType, Deserialize, Principal};
+use ethers_core::abi::{decode, ParamType, Token};
+use serde::Deserialize as SerdeDeserialize;
+
+use crate::action_batch::BatchMode;
+use crate::config::read_config;
+use crate::contract_registry::{registered_contract_request, TARGET_CONTRACT_NAME};
+use crate::cycles::RpcOperation;
+use crate::declarations::evm_rpc::RequestResult;
+use crate::eth_rpc::{from_hex, get_space_eth_address, nat_to_u256, rpc_request, to_hex};
+use crate::execution_payload::find_contract;
+use crate::roles::{has_role, Role};
+use crate::safe::safe_config;
+use crate::signing_space::space_of_proposal;
+use crate::transaction::TransactionRequest;
+use crate::PROPOSALS;
+
+// Enough for the output of typical calls and revert reasons
+const SIMULATION_RESPONSE_BYTES: u64 = 4096;
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Outcome of running a transaction with `eth_call` at the latest block.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SimulationResult {
+    pub from: String,
+    pub to: String,
+    pub success: bool,
+    /// Hex encoded return data, or the raw revert data if the call reverted.
+    pub output: String,
+    /// Decoded `Error(string)`, `Panic(uint256)` or custom error of the target's ABI.
+    pub revert_reason: Option<String>,
+}
+
+#[derive(SerdeDeserialize)]
+struct CallResponse {
+    result: Option<String>,
+    error: Option<CallError>,
+}
+
+#[derive(SerdeDeserialize)]
+struct CallError {
+    code: i64,
+    message: String,
+    data: Option<serde_json::Value>,
+}
+
+/// Runs `request` as an `eth_call` from `from`. Reverts are reported in the result, while
+/// failing RPC calls return an error.
+pub async fn simulate(
+    from: &str,
+    request: &TransactionRequest,
+    proposal_id: Option<u64>,
+) -> Result<SimulationResult, String> {
+    let gas = read_config(|c| c.gas_limit.clone());
+    let payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [
+            {
+                "from": from,
+                "to": request.to,
+                "value": format!("{:#x}", nat_to_u256(&request.value)),
+                "data": request.data.clone().unwrap_or_else(|| "0x".to_string()),
+                "gas": format!("{:#x}", nat_to_u256(&gas)),
+            },
+            "latest"
+        ],
+    })
+    .to_string();
+    let response = match rpc_request(
+        payload,
+        SIMULATION_RESPONSE_BYTES,
+        RpcOperation::EthCall,
+        proposal_id,
+    )
+    .await?
+    {
+        RequestResult::Ok(response) => response,
+        RequestResult::Err(err) => return Err(format!("Simulation failed: {err:?}")),
+    };
+    let response: CallResponse = serde_json::from_str(&response)
+        .map_err(|e| format!("Malformed simulation response: {e}"))?;
+
+    let mut result = SimulationResult {
+        from: from.to_string(),
+        to: request.to.clone(),
+        success: true,
+        output: "0x".to_string(),
+        revert_reason: None,
+    };
+    match (response.result, response.error) {
+        (_, Some(error)) => {
+            let data = match &error.data {
+                Some(serde_json::Value::String(data)) => Some(data.clone()),
+                _ => None,
+            };
+            if data.is_none() && !error.message.starts_with("execution reverted") {
+                return Err(format!(
+                    "JSON-RPC error code {}: {}",
+                    error.code, error.message
+                ));
+            }
+            result.success = false;
+            result.revert_reason = Some(match &data {
+                Some(data) => decode_revert(&request.to, &from_hex(data).unwrap_or_default()),
+                None => error.message,
+            });
+            result.output = data.unwrap_or_else(|| "0x".to_string());
+        }
+        (Some(output), None) => result.output = output,
+        (None, None) => return Err("Empty simulation response".to_string()),
+    }
+    Ok(result)
+}
+
+/// Decodes revert data into a readable reason, using the ABI of `to` for custom errors.
+pub fn decode_revert(to: &str, data: &[u8]) -> String {
+    if data.len() < 4 {
+        return "Reverted without a reason".to_string();
+    }
+    let (selector, args) = data.split_at(4);
+    if selector == ERROR_STRING_SELECTOR {
+        if let Ok(tokens) = decode(&[ParamType::String], args) {
+            if let Some(Token::String(message)) = tokens.first() {
+                return format!("Error(\"{message}\")");
+            }
+        }
+    }
+    if selector == PANIC_SELECTOR {
+        if let Ok(tokens) = decode(&[ParamType::Uint(256)], args) {
+            if let Some(Token::Uint(code)) = tokens.first() {
+                return format!("Panic({code:#x})");
+            }
+        }
+    }
+    let abi = find_contract(to).and_then(|contract| contract.parse_abi());
+    if let Ok(abi) = abi {
+        for error in abi.errors() {
+            if error.signature()[..4] != *selector {
+                continue;
+            }
+            if let Ok(tokens) = error.decode(args) {
+                let args = tokens.iter().map(ToString::to_string).collect::<Vec<_>>();
+                return format!("{}({})", error.name, args.join(", "));
+            }
+        }
+    }
+    format!("Unknown error {}", to_hex(data))
+}
+
+/// Simulates every transaction the execution of a proposal would send, as if it executed now.
+/// Actions of a sequential batch are simulated independently, so one that depends on the
+/// effects of an earlier action may revert here but succeed when executed.
+pub async fn simulate_proposal(
+    caller: Principal,
+    proposal_id: u64,
+) -> Result<Vec<SimulationResult>, String> {
+    let proposal = PROPOSALS
+        .with(|proposals| {
+            proposals
+                .borrow()
+                .iter()
+                .find(|p| p.id == proposal_id)
+                .cloned()
+        })
+        .ok_or_else(|| format!("Proposal {proposal_id} not found."))?;
+    // Every simulated call is a paid outcall, so only those who need the result may ask for it
+    if caller.to_text() != proposal.submitter && !has_role(&caller, Role::Admin) {
+        return Err("Only the submitter or an admin can simulate a proposal".to_string());
+    }
+    if proposal.canister_call.is_some() {
+        return Err("Canister calls cannot be simulated".to_string());
+    }
+    let canister_address =
+        get_space_eth_address(space_of_proposal(Some(proposal_id)).as_deref()).await;
+
+    // Safe-routed payloads are simulated as the call the Safe makes to the target
+    let mut calls = Vec::new();
+    if let Some(payload) = &proposal.execution_payload {
+        let from = match payload.safe {
+            Some(_) => safe_config()?.address,
+            None => canister_address.clone(),
+        };
+        calls.push((from, payload.transaction_request()?));
+    } else if let Some(transfer) = &proposal.treasury_transfer {
+        calls.push((canister_address.clone(), transfer.transaction_request()?));
+    } else if let Some(batch) = &proposal.action_batch {
+        match batch.mode {
+            BatchMode::Sequential => {
+                for action in &batch.actions {
+                    let from = match action.safe {
+                        Some(_) => safe_config()?.address,
+                        None => canister_address.clone(),
+                    };
+                    calls.push((from, action.transaction_request()?));
+                }
+            }
+            BatchMode::Multicall => {
+                calls.push((canister_address.clone(), batch.multicall_request()?));
+            }
+        }
+    } else {
+        let summary = Token::String(proposal.result_summary());
+        calls.push((
+            canister_address.clone(),
+            registered_contract_request(TARGET_CONTRACT_NAME, "storeString", &[summary])?,
+        ));
+    }
+
+    let mut results = Vec::new();
+    for (from, request) in calls {
+        results.push(simulate(&from, &request, Some(proposal_id)).await?);
+    }
+    Ok(results)
+}
+
+/// Fails with the revert reason if `request` would revert when sent from `from`.
+pub async fn check_does_not_revert(
+    from: &str,
+    request: &TransactionRequest,
+    proposal_id: Option<u64>,
+) -> Result<(), String> {
+    let result = simulate(from, request, proposal_id).await?;
+    match result.revert_reason {
+        Some(reason) if !result.success => Err(format!(
+            "Simulation of the call to {} reverted: {}",
+            request.to, reason
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "0x0000000000000000000000000000000000000001";
+
+    #[test]
+    fn decodes_error_strings() {
+        // `revert("Not enough Ether provided.")`, from the Solidity documentation
+        let data = from_hex("0x08c379a00000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000001a4e6f7420656e6f7567682045746865722070726f76696465642e000000000000").unwrap();
+        assert_eq!(
+            decode_revert(TARGET, &data),
+            "Error(\"Not enough Ether provided.\")"
+        );
+    }
+
+    #[test]
+    fn decodes_panics() {
+        // Arithmetic overflow
+        let data =
+            from_hex("0x4e487b710000000000000000000000000000000000000000000000000000000000000011")
+                .unwrap();
+        assert_eq!(decode_revert(TARGET, &data), "Panic(0x11)");
+    }
+
+    #[test]
+    fn reports_missing_and_unknown_reasons() {
+        assert_eq!(decode_revert(TARGET, &[]), "Reverted without a reason");
+        assert_eq!(
+            decode_revert(TARGET, &[0xde, 0xad, 0xbe, 0xef]),
+            "Unknown error 0xdeadbeef"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::api::time;
+
+use crate::config::read_config;
+use crate::{Proposal, PROPOSALS};
+
+/// Where a proposal is in its lifecycle.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalStatus {
+    Open,
+    /// Voting ended without enough support to execute the payload.
+    Rejected,
+    /// Accepted and waiting for its timelock to expire or for a retry.
+    Queued,
+    Vetoed,
+    Executing,
+    Executed,
+    /// All automatic execution attempts failed.
+    Failed,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ProposalSchedule {
+    pub status: ProposalStatus,
+    /// Earliest time the proposal executes, in nanoseconds since the epoch.
+    pub execution_eta: Option<u64>,
+    pub next_execution_attempt: Option<u64>,
+    pub veto_votes: Nat,
+    pub veto_threshold: Option<Nat>,
+}
+
+impl Proposal {
+    /// Whether the proposal is executed once voting ends. Proposals without a payload, transfer,
+    /// batch or canister call only record their result and are always executed.
+    pub fn will_execute(&self) -> bool {
+        (self.execution_payload.is_none()
+            && self.treasury_transfer.is_none()
+            && self.action_batch.is_none()
+            && self.canister_call.is_none())
+            || self.passed()
+    }
+
+    pub fn status(&self) -> ProposalStatus {
+        if self.is_open {
+            ProposalStatus::Open
+        } else if self.is_executed {
+            ProposalStatus::Executed
+        } else if self.is_vetoed {
+            ProposalStatus::Vetoed
+        } else if self.execution_in_progress
+            || (self.action_batch.is_some() && self.pending_transaction.is_some())
+        {
+            ProposalStatus::Executing
+        } else if !self.will_execute() {
+            ProposalStatus::Rejected
+        } else if !self.failed_executions.is_empty() && self.next_execution_attempt.is_none() {
+            ProposalStatus::Failed
+        } else {
+            ProposalStatus::Queued
+        }
+    }
+
+    pub fn schedule(&self) -> ProposalSchedule {
+        ProposalSchedule {
+            status: self.status(),
+            execution_eta: self.execution_eta,
+            next_execution_attempt: self.next_execution_attempt,
+            veto_votes: self.veto_votes.clone(),
+            veto_threshold: read_config(|c| c.governance.veto_threshold.clone()),
+        }
+    }
+
+    /// Whether the proposal can still be vetoed, which is until its timelock expires.
+    pub fn in_veto_window(&self) -> bool {
+        self.status() == ProposalStatus::Queued
+            && self.failed_executions.is_empty()
+            && self.execution_eta.is_some_and(|eta| time() < eta)
+    }
+
+    /// Closes voting and queues the proposal for execution once its timelock expires.
+    pub fn close(&mut self) {
+        self.is_open = false;
+        if self.will_execute() {
+            let timelock = read_config(|c| c.timelock(&self.proposal_type));
+            self.execution_eta = Some(time().saturating_add(timelock));
+        }
+    }
+}
+
+/// IDs of the queued proposals whose timelock has expired and that have not been attempted yet.
+pub fn proposals_due_for_execution() -> Vec<u64> {
+    let now = time();
+    PROPOSALS.with(|proposals| {
+        proposals
+            .borrow()
+            .iter()
+            .filter(|p| p.status() == ProposalStatus::Queued && p.failed_executions.is_empty())
+            .filter(|p| p.execution_eta.is_some_and(|eta| eta <= now))
+            .map(|p| p.id)
+            .collect()
+    })
+}
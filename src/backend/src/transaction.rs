@@ -0,0 +1,156 @@
+use candid::{CandidType, Deserialize, Nat};
+use ethers_core::types::transaction::eip1559::Eip1559TransactionRequest;
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::transaction::eip2930::{AccessList, AccessListItem, Eip2930TransactionRequest};
+use ethers_core::types::{Address, TransactionRequest as LegacyTransactionRequest, H256};
+use std::str::FromStr;
+
+use crate::config::read_config;
+use crate::eth_rpc::{
+    from_hex, gas_price, get_space_eth_address, nat_to_u256, next_id, parse_address,
+    send_transaction,
+};
+use crate::pending_transaction::PendingTransaction;
+use crate::signing_space::space_of_proposal;
+use crate::simulation::check_does_not_revert;
+
+/// The transaction envelopes the canister can sign.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionType {
+    /// Type 0 transaction with EIP-155 replay protection, for chains without EIP-1559.
+    Legacy,
+    /// Type 1 transaction with an [EIP-2930](https://eips.ethereum.org/EIPS/eip-2930) access list.
+    AccessList,
+    /// Type 2 [EIP-1559](https://eips.ethereum.org/EIPS/eip-1559) transaction.
+    Eip1559,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AccessListEntry {
+    pub address: String,
+    pub storage_keys: Vec<String>,
+}
+
+/// A transaction to be signed and sent by the canister. Nonce and fees are filled in when it
+/// is submitted.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransactionRequest {
+    pub to: String,
+    /// ETH to send, in wei.
+    pub value: Nat,
+    /// Hex encoded calldata, `None` for plain transfers.
+    pub data: Option<String>,
+    pub tx_type: TransactionType,
+    /// Ignored for legacy transactions.
+    pub access_list: Vec<AccessListEntry>,
+}
+
+/// Simulates `request` and, unless it reverts, fetches its nonce and fees, then signs and
+/// broadcasts it.
+pub async fn submit_transaction(
+    request: TransactionRequest,
+    proposal_id: Option<u64>,
+) -> Result<PendingTransaction, String> {
+    parse_address(&request.to)?;
+    if let Some(data) = &request.data {
+        from_hex(data).map_err(|e| format!("Invalid calldata: {e}"))?;
+    }
+    if request.tx_type == TransactionType::Legacy && !request.access_list.is_empty() {
+        return Err("Legacy transactions cannot carry an access list".to_string());
+    }
+    to_access_list(&request.access_list)?;
+    let from = get_space_eth_address(space_of_proposal(proposal_id).as_deref()).await;
+    check_does_not_revert(&from, &request, proposal_id).await?;
+
+    // Without EIP-1559 the fee cap is paid in full, so it follows the current gas price.
+    let (max_fee_per_gas, max_priority_fee_per_gas) = match request.tx_type {
+        TransactionType::Eip1559 => read_config(|c| {
+            (c.max_fee_per_gas.clone(), c.max_priority_fee_per_gas.clone())
+        }),
+        TransactionType::Legacy | TransactionType::AccessList => {
+            let gas_price = gas_price(proposal_id).await?;
+            (gas_price.clone(), gas_price)
+        }
+    };
+
+    let mut tx = PendingTransaction {
+        to: request.to,
+        value: request.value,
+        data: request.data,
+        tx_type: request.tx_type,
+        access_list: request.access_list,
+        nonce: next_id(proposal_id).await,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        hash: String::new(),
+        hashes: Vec::new(),
+        submitted_at: ic_cdk::api::time(),
+        is_cancellation: false,
+    };
+    tx.hash = send_transaction(&tx, proposal_id).await?;
+    tx.hashes = vec![tx.hash.clone()];
+    Ok(tx)
+}
+
+fn to_access_list(entries: &[AccessListEntry]) -> Result<AccessList, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let storage_keys = entry
+                .storage_keys
+                .iter()
+                .map(|key| {
+                    H256::from_str(key).map_err(|e| format!("Invalid storage key {key}: {e}"))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(AccessListItem {
+                address: parse_address(&entry.address)?,
+                storage_keys,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(AccessList)
+}
+
+impl PendingTransaction {
+    /// Builds the unsigned envelope of this transaction. For legacy and access list
+    /// transactions `max_fee_per_gas` is used as the gas price.
+    pub fn to_typed_transaction(&self) -> Result<TypedTransaction, String> {
+        let to = Address::from_str(&self.to).map_err(|e| format!("Invalid address: {e}"))?;
+        let data = match &self.data {
+            Some(data) => Some(from_hex(data).map_err(|e| format!("Invalid calldata: {e}"))?.into()),
+            None => None,
+        };
+        let (gas_limit, chain_id) = read_config(|c| (c.gas_limit.clone(), c.chain_id));
+        let legacy = LegacyTransactionRequest {
+            from: None,
+            to: Some(to.into()),
+            gas: Some(nat_to_u256(&gas_limit)),
+            gas_price: Some(nat_to_u256(&self.max_fee_per_gas)),
+            value: Some(nat_to_u256(&self.value)),
+            data,
+            nonce: Some(nat_to_u256(&self.nonce)),
+            chain_id: Some(chain_id.into()),
+        };
+
+        Ok(match self.tx_type {
+            TransactionType::Legacy => TypedTransaction::Legacy(legacy),
+            TransactionType::AccessList => TypedTransaction::Eip2930(Eip2930TransactionRequest {
+                tx: legacy,
+                access_list: to_access_list(&self.access_list)?,
+            }),
+            TransactionType::Eip1559 => TypedTransaction::Eip1559(Eip1559TransactionRequest {
+                chain_id: legacy.chain_id,
+                from: None,
+                to: legacy.to,
+                gas: legacy.gas,
+                value: legacy.value,
+                nonce: legacy.nonce,
+                data: legacy.data,
+                access_list: to_access_list(&self.access_list)?,
+                max_priority_fee_per_gas: Some(nat_to_u256(&self.max_priority_fee_per_gas)),
+                max_fee_per_gas: Some(nat_to_u256(&self.max_fee_per_gas)),
+            }),
+        })
+    }
+}
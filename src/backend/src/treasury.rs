@@ -0,0 +1,249 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Nat};
+use ethers_core::abi::{Contract, Token};
+use ic_cdk::api::time;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::config::read_config;
+use crate::cycles::RpcOperation;
+use crate::eth_rpc::{
+    batch_request, contract_call_request, from_hex, get_space_eth_address, nat_to_u256,
+    parse_address, to_hex, try_eth_balance_of,
+};
+use crate::pending_transaction::PendingTransaction;
+use crate::signing_space::space_of_type;
+use crate::transaction::{submit_transaction, TransactionRequest, TransactionType};
+use crate::TRANSFERS;
+
+/// Proposal type of treasury proposals, which also selects their timelock.
+pub const TREASURY_PROPOSAL_TYPE: &str = "treasury";
+// Estimated size of one `balanceOf` entry in a batch response
+const BALANCE_OF_RESPONSE_BYTES: u64 = 200;
+
+/// An ERC-20 token held by the treasury.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryToken {
+    pub symbol: String,
+    pub address: String,
+    pub decimals: u8,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TreasuryAsset {
+    Eth,
+    /// A configured token, by symbol.
+    Token(String),
+}
+
+/// Funds sent from the canister's address once a treasury proposal passes.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryTransfer {
+    pub recipient: String,
+    pub asset: TreasuryAsset,
+    /// In the smallest unit of the asset, e.g. wei.
+    pub amount: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// Broadcast, but not mined yet.
+    Sent,
+    Succeeded,
+    Reverted,
+    /// A cancellation was mined in its place, so no funds moved.
+    Cancelled,
+}
+
+/// An outgoing treasury transfer, recorded when its transaction is broadcast.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferRecord {
+    pub proposal_id: u64,
+    pub transfer: TreasuryTransfer,
+    /// The sent transaction, replaced by the mined one once it has a receipt.
+    pub tx_hash: String,
+    pub status: TransferStatus,
+    pub timestamp: u64,
+}
+
+impl Storable for TransferRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryBalance {
+    pub asset: TreasuryAsset,
+    pub decimals: u8,
+    pub balance: Result<Nat, String>,
+}
+
+fn erc20_abi() -> Contract {
+    ethers_core::abi::parse_abi(&[
+        "function transfer(address to, uint256 amount) returns (bool)",
+        "function balanceOf(address owner) view returns (uint256)",
+    ])
+    .expect("ERC-20 ABI is valid")
+}
+
+fn find_token(symbol: &str) -> Result<TreasuryToken, String> {
+    read_config(|c| {
+        c.treasury_tokens
+            .iter()
+            .flatten()
+            .find(|t| t.symbol == symbol)
+            .cloned()
+    })
+    .ok_or_else(|| format!("Token {symbol} is not a treasury token"))
+}
+
+impl TreasuryTransfer {
+    pub fn validate(&self) -> Result<(), String> {
+        parse_address(&self.recipient)?;
+        if self.amount == 0_u8 {
+            return Err("Transfer amount must not be zero".to_string());
+        }
+        if let TreasuryAsset::Token(symbol) = &self.asset {
+            parse_address(&find_token(symbol)?.address)?;
+        }
+        Ok(())
+    }
+
+    /// The plain ETH transfer or ERC-20 `transfer` call that moves the funds.
+    pub fn transaction_request(&self) -> Result<TransactionRequest, String> {
+        match &self.asset {
+            TreasuryAsset::Eth => Ok(TransactionRequest {
+                to: self.recipient.clone(),
+                value: self.amount.clone(),
+                data: None,
+                tx_type: TransactionType::Eip1559,
+                access_list: Vec::new(),
+            }),
+            TreasuryAsset::Token(symbol) => {
+                let token = find_token(symbol)?;
+                let args = [
+                    Token::Address(parse_address(&self.recipient)?),
+                    Token::Uint(nat_to_u256(&self.amount)),
+                ];
+                contract_call_request(token.address, &erc20_abi(), "transfer", &args)
+            }
+        }
+    }
+
+    /// Sends the transfer from the canister's address.
+    pub async fn execute(&self, proposal_id: u64) -> Result<PendingTransaction, String> {
+        let tx = submit_transaction(self.transaction_request()?, Some(proposal_id)).await?;
+        TRANSFERS.with(|t| {
+            let mut t = t.borrow_mut();
+            let id = t.len();
+            t.insert(
+                id,
+                TransferRecord {
+                    proposal_id,
+                    transfer: self.clone(),
+                    tx_hash: tx.hash.clone(),
+                    status: TransferStatus::Sent,
+                    timestamp: time(),
+                },
+            );
+        });
+        Ok(tx)
+    }
+}
+
+/// Settles the transfer of a proposal sent as one of `sent_hashes` with the receipt of
+/// `mined_hash`, the version of the transaction that was mined after fee bumps or a cancellation.
+pub fn record_transfer_receipt(
+    proposal_id: u64,
+    sent_hashes: &[String],
+    mined_hash: &str,
+    succeeded: bool,
+    cancelled: bool,
+) {
+    TRANSFERS.with(|t| {
+        let mut t = t.borrow_mut();
+        let found = t.iter().find(|(_, r)| {
+            r.proposal_id == proposal_id
+                && r.status == TransferStatus::Sent
+                && sent_hashes.contains(&r.tx_hash)
+        });
+        if let Some((id, mut record)) = found {
+            record.tx_hash = mined_hash.to_string();
+            record.status = if cancelled {
+                TransferStatus::Cancelled
+            } else if succeeded {
+                TransferStatus::Succeeded
+            } else {
+                TransferStatus::Reverted
+            };
+            t.insert(id, record);
+        }
+    });
+}
+
+/// Outgoing transfers, optionally only those of one proposal.
+pub fn transfers(proposal_id: Option<u64>) -> Vec<TransferRecord> {
+    TRANSFERS.with(|t| {
+        t.borrow()
+            .iter()
+            .map(|(_, r)| r)
+            .filter(|r| proposal_id.is_none_or(|id| r.proposal_id == id))
+            .collect()
+    })
+}
+
+/// Balances in ETH and every configured token of the address treasury proposals send from.
+pub async fn treasury_balances() -> Result<Vec<TreasuryBalance>, String> {
+    let address = get_space_eth_address(space_of_type(TREASURY_PROPOSAL_TYPE).as_deref()).await;
+    let tokens = read_config(|c| c.treasury_tokens.clone().unwrap_or_default());
+
+    let balance_of = erc20_abi().function("balanceOf").cloned().map_err(|e| e.to_string())?;
+    let data = balance_of
+        .encode_input(&[Token::Address(parse_address(&address)?)])
+        .map_err(|e| e.to_string())?;
+    let requests = tokens
+        .iter()
+        .map(|token| {
+            (
+                "eth_call",
+                serde_json::json!([{"to": token.address, "data": to_hex(&data)}, "latest"]),
+            )
+        })
+        .collect::<Vec<_>>();
+    let results = if requests.is_empty() {
+        Vec::new()
+    } else {
+        batch_request(&requests, BALANCE_OF_RESPONSE_BYTES, RpcOperation::EthCall, None).await?
+    };
+
+    let mut balances = vec![TreasuryBalance {
+        asset: TreasuryAsset::Eth,
+        decimals: 18,
+        balance: try_eth_balance_of(&address, "latest", None).await,
+    }];
+    balances.extend(tokens.into_iter().zip(results).map(|(token, result)| {
+        let balance = result.and_then(|hex| {
+            let output = from_hex(&hex).map_err(|e| e.to_string())?;
+            match balance_of.decode_output(&output).map_err(|e| e.to_string())?.first() {
+                Some(Token::Uint(balance)) => {
+                    Nat::from_str(&balance.to_string()).map_err(|e| e.to_string())
+                }
+                other => Err(format!("Unexpected balanceOf output: {other:?}")),
+            }
+        });
+        TreasuryBalance {
+            asset: TreasuryAsset::Token(token.symbol),
+            decimals: token.decimals,
+            balance,
+        }
+    }));
+    Ok(balances)
+}
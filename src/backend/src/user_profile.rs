@@ -1,7 +1,12 @@
-use candid::{CandidType, Decode, Deserialize, Encode};
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::println;
 use ic_stable_structures::{storable::Bound, Storable};
 use std::borrow::Cow;
 
+use crate::address_cache::invalidate_address;
+use crate::ens::ens_record;
+use crate::{ADDRESS_INDEX, LINKED_ADDRESSES, USER_PROFILES};
+
 const MAX_VALUE_SIZE: u32 = 500;
 
 #[derive(CandidType, Deserialize, Debug, Clone)]
@@ -9,6 +14,26 @@ pub struct UserProfile {
     pub address: String,
     pub name: String,
     pub avatar_url: String,
+    /// Verified ENS primary name of `address`, filled in from the ENS records when read.
+    pub ens_name: Option<String>,
+    /// The `avatar` text record of `ens_name`.
+    pub ens_avatar: Option<String>,
+}
+
+impl UserProfile {
+    /// Adds the cached ENS name and avatar of the profile's address.
+    pub fn with_ens(mut self) -> Self {
+        let record = ens_record(&self.address);
+        self.ens_name = record.as_ref().and_then(|r| r.name.clone());
+        self.ens_avatar = record.and_then(|r| r.avatar);
+        self
+    }
+
+    fn display_name(self) -> Option<String> {
+        self.ens_name
+            .or(Some(self.name))
+            .filter(|name| !name.is_empty())
+    }
 }
 
 impl Storable for UserProfile {
@@ -25,3 +50,86 @@ impl Storable for UserProfile {
         is_fixed_size: false,
     };
 }
+
+// Addresses are indexed in lowercase, since SIWE providers return them checksummed
+fn address_key(address: &str) -> String {
+    address.to_lowercase()
+}
+
+/// Stores the profile of `principal` and points its address at it. An address belongs to the
+/// principal that most recently proved control over it, so the profile of a principal that
+/// claimed it before is removed, together with its wallet link to the address.
+pub fn save_profile(principal: String, mut profile: UserProfile) {
+    // ENS data lives in its own map, since it would not fit the bounded profile
+    profile.ens_name = None;
+    profile.ens_avatar = None;
+    let key = address_key(&profile.address);
+    let previous_owner = ADDRESS_INDEX.with(|index| index.borrow().get(&key));
+    USER_PROFILES.with(|profiles| {
+        let mut profiles = profiles.borrow_mut();
+        if let Some(previous_owner) = previous_owner.filter(|owner| *owner != principal) {
+            println!(
+                "Address {} moved from {} to {}, removing the old profile",
+                profile.address, previous_owner, principal
+            );
+            profiles.remove(&previous_owner);
+            LINKED_ADDRESSES.with(|links| {
+                let mut links = links.borrow_mut();
+                if links
+                    .get(&previous_owner)
+                    .is_some_and(|linked| address_key(&linked) == key)
+                {
+                    links.remove(&previous_owner);
+                }
+            });
+            if let Ok(previous_owner) = Principal::from_text(&previous_owner) {
+                invalidate_address(&previous_owner);
+            }
+        }
+        if let Some(old) = profiles.insert(principal.clone(), profile) {
+            let old_key = address_key(&old.address);
+            if old_key != key {
+                ADDRESS_INDEX.with(|index| index.borrow_mut().remove(&old_key));
+            }
+        }
+    });
+    ADDRESS_INDEX.with(|index| index.borrow_mut().insert(key, principal));
+}
+
+/// The principal that claimed `address` and its profile.
+pub fn profile_by_address(address: &str) -> Option<(String, UserProfile)> {
+    let principal = ADDRESS_INDEX.with(|index| index.borrow().get(&address_key(address)))?;
+    let profile = USER_PROFILES.with(|profiles| profiles.borrow().get(&principal))?;
+    Some((principal, profile.with_ens()))
+}
+
+/// Name to show for a principal: the verified ENS name of its address, or else the name it
+/// saved in its profile.
+pub fn display_name(principal: &str) -> Option<String> {
+    USER_PROFILES
+        .with(|profiles| profiles.borrow().get(&principal.to_string()))
+        .and_then(|profile| profile.with_ens().display_name())
+}
+
+/// Name to show for the principal that claimed `address`, see `display_name`.
+pub fn display_name_of_address(address: &str) -> Option<String> {
+    profile_by_address(address).and_then(|(_, profile)| profile.display_name())
+}
+
+/// Indexes the profiles saved before the index existed. Of several principals claiming the same
+/// address, the last one in principal order is indexed, but no profile is removed. Conflicts are
+/// only resolved when one of them saves its profile again.
+pub fn rebuild_address_index() {
+    USER_PROFILES.with(|profiles| {
+        ADDRESS_INDEX.with(|index| {
+            let mut index = index.borrow_mut();
+            for (principal, profile) in profiles.borrow().iter() {
+                index.insert(address_key(&profile.address), principal);
+            }
+        })
+    });
+}
+
+pub fn address_index_is_empty() -> bool {
+    ADDRESS_INDEX.with(|index| index.borrow().is_empty())
+}
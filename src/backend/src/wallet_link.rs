@@ -0,0 +1,197 @@
+use candid::Principal;
+use ethers_core::abi::{Contract, Token};
+use ethers_core::types::Address;
+use ethers_core::utils::{hash_message, keccak256, to_checksum};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::{id, time};
+use ic_cdk::println;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+
+use crate::address_cache::{invalidate_address, update_cached_address};
+use crate::ens::resolve_address;
+use crate::eth_rpc::{eth_call_batch, parse_address};
+use crate::{LINKED_ADDRESSES, LINK_CHALLENGES};
+
+// Same as `sign_in_expires_in` of the SIWE provider in the Makefile
+const LINK_MESSAGE_EXPIRES_IN: u64 = 5 * 60 * 1_000_000_000;
+// Returned by `isValidSignature` for valid signatures, as defined by EIP-1271
+const EIP1271_MAGIC_VALUE: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+const EIP1271_RESPONSE_BYTES: u64 = 1000;
+
+/// A message issued to a principal, which it has to get signed by the wallet it links.
+#[derive(Clone, Debug)]
+pub struct LinkChallenge {
+    pub message: String,
+    pub expires_at: u64,
+}
+
+/// Issues a new message for `principal` to sign with `personal_sign`, replacing any earlier one.
+pub async fn issue_link_message(principal: Principal) -> Result<String, String> {
+    // All anonymous callers share one principal, which must not own an address
+    if principal == Principal::anonymous() {
+        return Err("Anonymous principals cannot link an address".to_string());
+    }
+    let (nonce,) = raw_rand()
+        .await
+        .map_err(|(code, msg)| format!("Failed to generate a nonce: {:?} {}", code, msg))?;
+    let now = time();
+    let message = format!(
+        "Link this Ethereum account to the Internet Computer principal {} in canister {}.\n\nNonce: {}\nIssued at: {}",
+        principal,
+        id(),
+        hex::encode(&nonce[..16]),
+        now
+    );
+    LINK_CHALLENGES.with(|challenges| {
+        challenges.borrow_mut().insert(
+            principal,
+            LinkChallenge {
+                message: message.clone(),
+                expires_at: now.saturating_add(LINK_MESSAGE_EXPIRES_IN),
+            },
+        )
+    });
+    Ok(message)
+}
+
+/// Recovers the signer of an EIP-191 `personal_sign` signature over `message`.
+pub fn recover_signer(message: &str, signature: &str) -> Result<String, String> {
+    let bytes = hex::decode(signature.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    if bytes.len() != 65 {
+        return Err("Signatures must be 65 bytes long".to_string());
+    }
+    let signature =
+        Signature::try_from(&bytes[..64]).map_err(|e| format!("Invalid signature: {}", e))?;
+    // Wallets return `v` as 27 or 28, some hardware wallets as 0 or 1
+    let v = match bytes[64] {
+        v @ 0..=1 => v,
+        v @ 27..=28 => v - 27,
+        v => return Err(format!("Invalid signature recovery id {}", v)),
+    };
+    let recid = RecoveryId::try_from(v).map_err(|e| format!("Invalid signature: {}", e))?;
+    let prehash = hash_message(message);
+    let key = VerifyingKey::recover_from_prehash(prehash.as_bytes(), &signature, recid)
+        .map_err(|e| format!("Failed to recover the signer: {}", e))?;
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    Ok(to_checksum(&Address::from_slice(&hash[12..32]), None))
+}
+
+fn eip1271_abi() -> Contract {
+    ethers_core::abi::parse_abi(&[
+        "function isValidSignature(bytes32 hash, bytes signature) view returns (bytes4)",
+    ])
+    .expect("EIP-1271 ABI is valid")
+}
+
+/// Asks the contract wallet at `wallet` whether `signature` is a valid signature of `message`,
+/// hashed as for `personal_sign`, at the latest block.
+pub async fn verify_contract_signature(
+    wallet: &str,
+    message: &str,
+    signature: &str,
+) -> Result<(), String> {
+    let signature = hex::decode(signature.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    let args = vec![
+        Token::FixedBytes(hash_message(message).as_bytes().to_vec()),
+        Token::Bytes(signature),
+    ];
+    let result = eth_call_batch(
+        wallet,
+        &eip1271_abi(),
+        "isValidSignature",
+        &[args],
+        "latest",
+        EIP1271_RESPONSE_BYTES,
+        None,
+    )
+    .await?
+    .pop()
+    .ok_or("Missing isValidSignature response")?
+    .map_err(|e| format!("{} did not validate the signature: {}", wallet, e))?;
+    match result.first() {
+        Some(Token::FixedBytes(value)) if value[..] == EIP1271_MAGIC_VALUE => Ok(()),
+        _ => Err(format!("{} rejected the signature", wallet)),
+    }
+}
+
+/// Checks the signature over the pending message of `principal` and links the signer to it. If
+/// `wallet` is set, the signature is checked by that contract wallet through EIP-1271 and the
+/// wallet is linked instead.
+pub async fn link_address(
+    principal: Principal,
+    signature: &str,
+    wallet: Option<String>,
+) -> Result<String, String> {
+    // Removed before any call, so that a message cannot be used twice
+    let challenge = LINK_CHALLENGES
+        .with(|challenges| challenges.borrow_mut().remove(&principal))
+        .ok_or("No link message was issued to the caller")?;
+    if challenge.expires_at <= time() {
+        return Err("The link message has expired".to_string());
+    }
+    let address = match wallet {
+        None => recover_signer(&challenge.message, signature)?,
+        Some(wallet) => {
+            let wallet = to_checksum(&parse_address(&resolve_address(&wallet).await?)?, None);
+            verify_contract_signature(&wallet, &challenge.message, signature).await?;
+            wallet
+        }
+    };
+    LINKED_ADDRESSES.with(|links| {
+        links
+            .borrow_mut()
+            .insert(principal.to_text(), address.clone())
+    });
+    update_cached_address(&principal, address.clone());
+    println!("{} linked the address {}", principal, address);
+    Ok(address)
+}
+
+/// The address `principal` linked by signing a message, if any.
+pub fn linked_address(principal: &Principal) -> Option<String> {
+    LINKED_ADDRESSES.with(|links| links.borrow().get(&principal.to_text()))
+}
+
+pub fn unlink_address(principal: &Principal) -> Result<String, String> {
+    let address = LINKED_ADDRESSES
+        .with(|links| links.borrow_mut().remove(&principal.to_text()))
+        .ok_or("The caller has not linked an address")?;
+    invalidate_address(principal);
+    println!("{} unlinked the address {}", principal, address);
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Signature of "Some data" by the key from the web3.js `eth.accounts.sign` documentation
+    const SIGNATURE: &str = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+    const SIGNER: &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+
+    #[test]
+    fn recovers_personal_sign_signer() {
+        assert_eq!(recover_signer("Some data", SIGNATURE).unwrap(), SIGNER);
+    }
+
+    #[test]
+    fn accepts_recovery_ids_without_offset() {
+        let signature = format!("{}01", &SIGNATURE[..SIGNATURE.len() - 2]);
+        assert_eq!(recover_signer("Some data", &signature).unwrap(), SIGNER);
+    }
+
+    #[test]
+    fn other_messages_recover_other_signers() {
+        assert_ne!(recover_signer("Other data", SIGNATURE).unwrap(), SIGNER);
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        assert!(recover_signer("Some data", &SIGNATURE[..SIGNATURE.len() - 2]).is_err());
+        let signature = format!("{}05", &SIGNATURE[..SIGNATURE.len() - 2]);
+        assert!(recover_signer("Some data", &signature).is_err());
+    }
+}
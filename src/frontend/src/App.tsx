@@ -1,9 +1,30 @@
+import { useEffect } from "react";
+import { useSiweIdentity } from "ic-use-siwe-identity";
+
 import Header from "./components/header/Header";
 import GitHubIcon from "./components/GitHubIcon";
+import { useActor } from "./ic/Actors";
 
 import Voting from "./components/Voting"
 
 function App() {
+  const { actor } = useActor();
+  const { delegationChain } = useSiweIdentity();
+
+  // Let the backend cache the caller's address for as long as the delegation is valid
+  useEffect(() => {
+    const expirations = delegationChain?.delegations.map(
+      (delegation) => delegation.delegation.expiration
+    );
+    if (!actor || !expirations?.length) {
+      return;
+    }
+    const expiration = expirations.reduce((a, b) => (a < b ? a : b));
+    actor.start_session(expiration).catch((error: unknown) => {
+      console.error("Failed to start the session:", error);
+    });
+  }, [actor, delegationChain]);
+
   return (
     <div className="flex flex-col items-center w-full">
       <Header />
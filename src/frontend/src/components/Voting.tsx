@@ -55,8 +55,12 @@ export default function Voting() {
     }
     setIsSubmitting(true); // Start the spinner
     try {
-      const proposalId = await actor.submit_proposal(title, description, type, BigInt(duration_seconds));
-      console.log(`Proposal submitted successfully with ID: ${proposalId}`);
+      const result = await actor.submit_proposal(title, description, type, BigInt(duration_seconds), []);
+      if ('Err' in result) {
+        console.error("Proposal was rejected:", result.Err);
+      } else {
+        console.log(`Proposal submitted successfully with ID: ${result.Ok}`);
+      }
 
       fetchProposals();
     } catch (error) {
@@ -179,7 +183,7 @@ export default function Voting() {
                   <p className="text-gray-300"><span className="font-semibold">Description:</span> {proposal.description}</p>
 
                   {/* Submitter ICP principal and ETH address */}
-                  <div className="text-gray-300 font-semibold">Submitter Details:</div>
+                  <div className="text-gray-300 font-semibold">Submitter Details:{proposal.submitter_name.length > 0 && ` ${proposal.submitter_name[0]}`}</div>
                   <PrincipalPill principal={proposal.submitter} className="bg-zinc-700" />
                   <AddressPill address={proposal.submitter_eth_address} className="bg-zinc-700" />
 
@@ -260,7 +264,7 @@ export default function Voting() {
                 <p className="text-gray-300"><span className="font-semibold">Description:</span> {proposal.description}</p>
 
                 {/* Submitter ICP principal and ETH address */}
-                <div className="text-gray-300 font-semibold">Submitter Details:</div>
+                <div className="text-gray-300 font-semibold">Submitter Details:{proposal.submitter_name.length > 0 && ` ${proposal.submitter_name[0]}`}</div>
                 <PrincipalPill principal={proposal.submitter} className="bg-zinc-700" />
                 <AddressPill address={proposal.submitter_eth_address} className="bg-zinc-700" />
 
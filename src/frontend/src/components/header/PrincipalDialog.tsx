@@ -2,6 +2,7 @@ import Button from "../ui/Button";
 import Dialog from "../ui/Dialog";
 import { Dialog as HeadlessDialog } from "@headlessui/react";
 import { useSiweIdentity } from "ic-use-siwe-identity";
+import { useActor } from "../../ic/Actors";
 
 type SessionDialogProps = {
   isOpen: boolean;
@@ -14,6 +15,17 @@ export default function PrincipalDialog({
 }: SessionDialogProps) {
   const { clear, identity } = useSiweIdentity();
 
+  const { actor } = useActor();
+
+  // Let the backend forget the cached address before the session is cleared.
+  const logout = async () => {
+    try {
+      await actor?.logout();
+    } finally {
+      clear();
+    }
+  };
+
   if (!identity) return null;
 
   return (
@@ -33,7 +45,7 @@ export default function PrincipalDialog({
         <Button onClick={() => setIsOpen(false)} variant="outline">
           Close
         </Button>
-        <Button onClick={clear}>Logout</Button>
+        <Button onClick={logout}>Logout</Button>
       </div>
     </Dialog>
   );
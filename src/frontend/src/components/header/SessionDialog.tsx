@@ -2,6 +2,7 @@ import Button from "../ui/Button";
 import Dialog from "../ui/Dialog";
 import { Dialog as HeadlessDialog } from "@headlessui/react";
 import { useSiweIdentity } from "ic-use-siwe-identity";
+import { useActor } from "../../ic/Actors";
 
 type SessionDialogProps = {
   isOpen: boolean;
@@ -21,6 +22,17 @@ export default function SessionDialog({
 }: SessionDialogProps) {
   const { clear, identity, delegationChain } = useSiweIdentity();
 
+  const { actor } = useActor();
+
+  // Let the backend forget the cached address before the session is cleared.
+  const logout = async () => {
+    try {
+      await actor?.logout();
+    } finally {
+      clear();
+    }
+  };
+
   if (!identity) return null;
 
   return (
@@ -52,7 +64,7 @@ export default function SessionDialog({
         <Button onClick={() => setIsOpen(false)} variant="outline">
           Close
         </Button>
-        <Button onClick={clear}>Logout</Button>
+        <Button onClick={logout}>Logout</Button>
       </div>
     </Dialog>
   );
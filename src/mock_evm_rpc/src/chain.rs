@@ -0,0 +1,267 @@
+use candid::{CandidType, Deserialize, Nat};
+use std::collections::BTreeMap;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::types::{BlockTag, LogEntry, SendRawTransactionStatus, TransactionReceipt};
+
+const DEFAULT_GAS_PRICE: u64 = 1_000_000_000;
+const GAS_USED: u64 = 21_000;
+
+/// A raw transaction received through `eth_sendRawTransaction`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SentTransaction {
+    pub hash: String,
+    pub raw: String,
+    pub nonce: u64,
+    /// Receipt status, 1 for success and 0 for a revert.
+    pub status: Nat,
+    /// Block the transaction was mined in, `None` while it is pending.
+    pub block_number: Option<u64>,
+    /// Set when a later transaction with the same nonce replaced it before it was mined.
+    pub replaced: bool,
+}
+
+/// The simulated chain. All transactions are assumed to come from the same sender, so there
+/// is a single nonce sequence.
+pub struct Chain {
+    pub block_number: u64,
+    pub gas_price: Nat,
+    /// Balances by lowercase address.
+    pub balances: BTreeMap<String, Nat>,
+    /// `eth_call` results by lowercase contract address and calldata or 4-byte selector.
+    pub call_results: BTreeMap<(String, String), String>,
+    /// Revert data of `eth_call`s, keyed like `call_results` and taking precedence over them.
+    pub call_reverts: BTreeMap<(String, String), String>,
+    pub logs: Vec<LogEntry>,
+    /// Mines every transaction into a new block as soon as it is received.
+    pub auto_mine: bool,
+    pub transactions: Vec<SentTransaction>,
+}
+
+impl Default for Chain {
+    fn default() -> Self {
+        Self {
+            block_number: 1,
+            gas_price: Nat::from(DEFAULT_GAS_PRICE),
+            balances: BTreeMap::new(),
+            call_results: BTreeMap::new(),
+            call_reverts: BTreeMap::new(),
+            logs: Vec::new(),
+            auto_mine: true,
+            transactions: Vec::new(),
+        }
+    }
+}
+
+impl Chain {
+    pub fn resolve_block(&self, tag: &BlockTag) -> u64 {
+        match tag {
+            BlockTag::Earliest => 0,
+            BlockTag::Number(n) => u64::try_from(n.0.clone()).unwrap_or(u64::MAX),
+            BlockTag::Latest | BlockTag::Safe | BlockTag::Finalized | BlockTag::Pending => {
+                self.block_number
+            }
+        }
+    }
+
+    pub fn balance_of(&self, address: &str) -> Nat {
+        self.balances
+            .get(&address.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn call_result(&self, to: &str, data: &str) -> Option<String> {
+        scripted_call(&self.call_results, to, data)
+    }
+
+    pub fn call_revert(&self, to: &str, data: &str) -> Option<String> {
+        scripted_call(&self.call_reverts, to, data)
+    }
+
+    /// The next nonce, counting pending transactions if `pending` is set.
+    pub fn transaction_count(&self, pending: bool) -> u64 {
+        self.transactions
+            .iter()
+            .filter(|tx| !tx.replaced && (pending || tx.block_number.is_some()))
+            .map(|tx| tx.nonce + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn send_raw_transaction(&mut self, raw: &str) -> Result<SendRawTransactionStatus, String> {
+        let bytes = hex::decode(raw.trim_start_matches("0x"))
+            .map_err(|e| format!("Invalid transaction hex: {e}"))?;
+        let nonce = decode_nonce(&bytes)?;
+        let hash = format!("0x{}", hex::encode(keccak256(&bytes)));
+
+        if self.transactions.iter().any(|tx| tx.hash == hash) {
+            return Ok(SendRawTransactionStatus::Ok(Some(hash)));
+        }
+        if nonce < self.transaction_count(false) {
+            return Ok(SendRawTransactionStatus::NonceTooLow);
+        }
+        if nonce > self.transaction_count(true) {
+            return Ok(SendRawTransactionStatus::NonceTooHigh);
+        }
+        for tx in self.transactions.iter_mut() {
+            if tx.nonce == nonce && tx.block_number.is_none() {
+                tx.replaced = true;
+            }
+        }
+        self.transactions.push(SentTransaction {
+            hash: hash.clone(),
+            raw: raw.to_string(),
+            nonce,
+            status: Nat::from(1u8),
+            block_number: None,
+            replaced: false,
+        });
+        if self.auto_mine {
+            self.mine_block();
+        }
+        Ok(SendRawTransactionStatus::Ok(Some(hash)))
+    }
+
+    /// Starts a new block holding all pending transactions.
+    pub fn mine_block(&mut self) -> u64 {
+        self.block_number += 1;
+        let block_number = self.block_number;
+        for tx in self.transactions.iter_mut() {
+            if tx.block_number.is_none() && !tx.replaced {
+                tx.block_number = Some(block_number);
+            }
+        }
+        block_number
+    }
+
+    pub fn receipt(&self, hash: &str) -> Option<TransactionReceipt> {
+        let hash = hash.to_lowercase();
+        let tx = self.transactions.iter().find(|tx| tx.hash == hash)?;
+        let block_number = tx.block_number?;
+        let transaction_index = self
+            .transactions
+            .iter()
+            .filter(|other| other.block_number == Some(block_number))
+            .position(|other| other.hash == hash)
+            .unwrap_or_default();
+        let tx_type = match hex::decode(tx.raw.trim_start_matches("0x")) {
+            Ok(bytes) if bytes.first().is_some_and(|b| *b <= 0x7f) => format!("{:#x}", bytes[0]),
+            _ => "0x0".to_string(),
+        };
+
+        Some(TransactionReceipt {
+            to: String::new(),
+            status: tx.status.clone(),
+            transactionHash: tx.hash.clone(),
+            blockNumber: Nat::from(block_number),
+            from: String::new(),
+            logs: Vec::new(),
+            blockHash: block_hash(block_number),
+            r#type: tx_type,
+            transactionIndex: Nat::from(transaction_index),
+            effectiveGasPrice: self.gas_price.clone(),
+            logsBloom: format!("0x{}", "00".repeat(256)),
+            contractAddress: None,
+            gasUsed: Nat::from(GAS_USED),
+        })
+    }
+
+    pub fn logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        addresses: &[String],
+        topics: &[Vec<String>],
+    ) -> Vec<LogEntry> {
+        self.logs
+            .iter()
+            .filter(|log| {
+                let block = log
+                    .blockNumber
+                    .as_ref()
+                    .and_then(|n| u64::try_from(n.0.clone()).ok())
+                    .unwrap_or_default();
+                (from_block..=to_block).contains(&block)
+                    && (addresses.is_empty()
+                        || addresses.iter().any(|a| a.eq_ignore_ascii_case(&log.address)))
+                    && topics.iter().enumerate().all(|(i, alternatives)| {
+                        alternatives.is_empty()
+                            || log.topics.get(i).is_some_and(|topic| {
+                                alternatives.iter().any(|a| a.eq_ignore_ascii_case(topic))
+                            })
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn block_hash(block_number: u64) -> String {
+    format!("0x{}", hex::encode(keccak256(&block_number.to_be_bytes())))
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+/// Reads the nonce of a signed transaction. Typed transactions start with their type byte
+/// and carry the chain ID in front of the nonce.
+fn decode_nonce(raw: &[u8]) -> Result<u64, String> {
+    let (payload, nonce_index) = match raw.first() {
+        Some(0x01) | Some(0x02) => (&raw[1..], 1),
+        Some(_) => (raw, 0),
+        None => return Err("Empty transaction".to_string()),
+    };
+    let (mut fields, _) = rlp_item(payload)?;
+    for _ in 0..nonce_index {
+        fields = rlp_item(fields)?.1;
+    }
+    let (nonce, _) = rlp_item(fields)?;
+    if nonce.len() > 8 {
+        return Err("Nonce does not fit in u64".to_string());
+    }
+    Ok(nonce.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+}
+
+/// Splits the first RLP item off `data` and returns its payload and the remaining bytes.
+fn rlp_item(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let prefix = *data.first().ok_or("Unexpected end of RLP data")?;
+    let (offset, len) = match prefix {
+        0x00..=0x7f => return Ok((&data[..1], &data[1..])),
+        0x80..=0xb7 => (1, (prefix - 0x80) as usize),
+        0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+        0xb8..=0xbf | 0xf8..=0xff => {
+            let len_of_len = (prefix - if prefix >= 0xf8 { 0xf7 } else { 0xb7 }) as usize;
+            let len_bytes = data
+                .get(1..1 + len_of_len)
+                .ok_or("Unexpected end of RLP data")?;
+            let len = len_bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            (1 + len_of_len, len)
+        }
+    };
+    let end = offset + len;
+    if data.len() < end {
+        return Err("Unexpected end of RLP data".to_string());
+    }
+    Ok((&data[offset..end], &data[end..]))
+}
+
+// Looks up a scripted call by its full calldata, then by its selector
+fn scripted_call(
+    scripts: &BTreeMap<(String, String), String>,
+    to: &str,
+    data: &str,
+) -> Option<String> {
+    let to = to.to_lowercase();
+    let data = data.to_lowercase();
+    let selector = data.get(..10).unwrap_or(&data).to_string();
+    scripts
+        .get(&(to.clone(), data))
+        .or_else(|| scripts.get(&(to, selector)))
+        .cloned()
+}
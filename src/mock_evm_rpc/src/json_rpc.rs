@@ -0,0 +1,67 @@
+use candid::Nat;
+use serde_json::{json, Value};
+
+use crate::chain::Chain;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EXECUTION_REVERTED: i64 = 3;
+
+/// Answers a JSON-RPC request or batch of requests against the simulated chain.
+pub fn handle_payload(chain: &Chain, payload: &str) -> Result<String, String> {
+    let request: Value =
+        serde_json::from_str(payload).map_err(|e| format!("Invalid JSON-RPC payload: {e}"))?;
+    let response = match &request {
+        Value::Array(requests) => {
+            Value::Array(requests.iter().map(|r| handle_request(chain, r)).collect())
+        }
+        request => handle_request(chain, request),
+    };
+    Ok(response.to_string())
+}
+
+fn handle_request(chain: &Chain, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let params = &request["params"];
+    let result = match request["method"].as_str().unwrap_or_default() {
+        "eth_blockNumber" => Ok(quantity(&Nat::from(chain.block_number))),
+        "eth_gasPrice" => Ok(quantity(&chain.gas_price)),
+        "eth_getBalance" => params[0]
+            .as_str()
+            .map(|address| quantity(&chain.balance_of(address)))
+            .ok_or((INVALID_PARAMS, "Expected an address".to_string())),
+        "eth_call" => match (params[0]["to"].as_str(), params[0]["data"].as_str()) {
+            (Some(to), Some(data)) => {
+                if let Some(revert_data) = chain.call_revert(to, data) {
+                    return json!({"jsonrpc": "2.0", "id": id, "error": {
+                        "code": EXECUTION_REVERTED,
+                        "message": "execution reverted",
+                        "data": revert_data,
+                    }});
+                }
+                // Unscripted calls succeed without output, like calls to an account without code
+                Ok(chain.call_result(to, data).unwrap_or_else(|| "0x".to_string()))
+            }
+            _ => Err((INVALID_PARAMS, "Expected a call object".to_string())),
+        },
+        "eth_getTransactionCount" => {
+            let pending = params[1].as_str() == Some("pending");
+            Ok(quantity(&Nat::from(chain.transaction_count(pending))))
+        }
+        method => Err((
+            METHOD_NOT_FOUND,
+            format!("Method {method} is not supported by the mock"),
+        )),
+    };
+
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => {
+            json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+        }
+    }
+}
+
+fn quantity(value: &Nat) -> String {
+    format!("{:#x}", value.0)
+}
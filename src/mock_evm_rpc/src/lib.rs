@@ -0,0 +1,200 @@
+//! Offline stand-in for the EVM RPC canister. It answers the calls the backend makes from a
+//! scriptable in-memory chain instead of forwarding them to Ethereum providers.
+use candid::Nat;
+use ic_cdk::api::call::{msg_cycles_accept128, msg_cycles_available128};
+use ic_cdk::{query, update};
+use std::cell::RefCell;
+
+mod chain;
+mod json_rpc;
+mod types;
+
+use chain::{Chain, SentTransaction};
+use types::*;
+
+// Flat price quoted for every request, low enough for freshly created local canisters
+const REQUEST_COST: u128 = 1_000_000;
+
+thread_local! {
+    static CHAIN: RefCell<Chain> = RefCell::new(Chain::default());
+}
+
+fn read_chain<R>(f: impl FnOnce(&Chain) -> R) -> R {
+    CHAIN.with(|c| f(&c.borrow()))
+}
+
+fn mutate_chain<R>(f: impl FnOnce(&mut Chain) -> R) -> R {
+    CHAIN.with(|c| f(&mut c.borrow_mut()))
+}
+
+// Keeps the attached cycles like the real canister, so cycle accounting can be exercised
+fn accept_cycles() {
+    msg_cycles_accept128(msg_cycles_available128());
+}
+
+fn validation_error(message: String) -> RpcError {
+    RpcError::ValidationError(ValidationError::Custom(message))
+}
+
+#[query(name = "requestCost")]
+fn request_cost(_service: RpcService, _json: String, _max_response_bytes: u64) -> RequestCostResult {
+    RequestCostResult::Ok(Nat::from(REQUEST_COST))
+}
+
+#[update]
+fn request(_service: RpcService, json: String, _max_response_bytes: u64) -> RequestResult {
+    accept_cycles();
+    match read_chain(|c| json_rpc::handle_payload(c, &json)) {
+        Ok(response) => RequestResult::Ok(response),
+        Err(e) => RequestResult::Err(validation_error(e)),
+    }
+}
+
+#[update(name = "eth_getTransactionCount")]
+fn eth_get_transaction_count(
+    _services: RpcServices,
+    _config: Option<RpcConfig>,
+    args: GetTransactionCountArgs,
+) -> MultiGetTransactionCountResult {
+    accept_cycles();
+    let pending = matches!(args.block, BlockTag::Pending);
+    let count = read_chain(|c| c.transaction_count(pending));
+    MultiGetTransactionCountResult::Consistent(GetTransactionCountResult::Ok(Nat::from(count)))
+}
+
+#[update(name = "eth_sendRawTransaction")]
+fn eth_send_raw_transaction(
+    _services: RpcServices,
+    _config: Option<RpcConfig>,
+    raw_signed_transaction_hex: String,
+) -> MultiSendRawTransactionResult {
+    accept_cycles();
+    let result = match mutate_chain(|c| c.send_raw_transaction(&raw_signed_transaction_hex)) {
+        Ok(status) => SendRawTransactionResult::Ok(status),
+        Err(e) => SendRawTransactionResult::Err(validation_error(e)),
+    };
+    MultiSendRawTransactionResult::Consistent(result)
+}
+
+#[update(name = "eth_getTransactionReceipt")]
+fn eth_get_transaction_receipt(
+    _services: RpcServices,
+    _config: Option<RpcConfig>,
+    hash: String,
+) -> MultiGetTransactionReceiptResult {
+    accept_cycles();
+    let receipt = read_chain(|c| c.receipt(&hash));
+    MultiGetTransactionReceiptResult::Consistent(GetTransactionReceiptResult::Ok(receipt))
+}
+
+#[update(name = "eth_getLogs")]
+fn eth_get_logs(
+    _services: RpcServices,
+    _config: Option<RpcConfig>,
+    args: GetLogsArgs,
+) -> MultiGetLogsResult {
+    accept_cycles();
+    let logs = read_chain(|c| {
+        let from_block = args
+            .fromBlock
+            .as_ref()
+            .map_or(c.block_number, |tag| c.resolve_block(tag));
+        let to_block = args
+            .toBlock
+            .as_ref()
+            .map_or(c.block_number, |tag| c.resolve_block(tag));
+        c.logs(
+            from_block,
+            to_block,
+            &args.addresses,
+            &args.topics.unwrap_or_default(),
+        )
+    });
+    MultiGetLogsResult::Consistent(GetLogsResult::Ok(logs))
+}
+
+// Scripting interface, used by tests to set up the chain state
+
+#[update]
+fn set_block_number(block_number: u64) {
+    mutate_chain(|c| c.block_number = block_number);
+}
+
+#[update]
+fn set_gas_price(gas_price: Nat) {
+    mutate_chain(|c| c.gas_price = gas_price);
+}
+
+#[update]
+fn set_balance(address: String, balance: Nat) {
+    mutate_chain(|c| c.balances.insert(address.to_lowercase(), balance));
+}
+
+/// Scripts the hex encoded result of `eth_call`s to `to`. `data` is either the full
+/// calldata or a 4-byte function selector matching any arguments.
+#[update]
+fn set_call_result(to: String, data: String, result: String) {
+    mutate_chain(|c| {
+        c.call_results
+            .insert((to.to_lowercase(), data.to_lowercase()), result)
+    });
+}
+
+/// Makes `eth_call`s to `to` revert with the hex encoded `revert_data`, matched like
+/// `set_call_result`.
+#[update]
+fn set_call_revert(to: String, data: String, revert_data: String) {
+    mutate_chain(|c| {
+        c.call_reverts
+            .insert((to.to_lowercase(), data.to_lowercase()), revert_data)
+    });
+}
+
+/// Adds a log returned by `eth_getLogs`. It is placed in the current block unless it
+/// specifies a block number.
+#[update]
+fn add_log(mut log: LogEntry) {
+    mutate_chain(|c| {
+        if log.blockNumber.is_none() {
+            log.blockNumber = Some(Nat::from(c.block_number));
+        }
+        if log.blockHash.is_none() {
+            let block_number = u64::try_from(log.blockNumber.clone().unwrap().0).unwrap_or_default();
+            log.blockHash = Some(chain::block_hash(block_number));
+        }
+        c.logs.push(log)
+    });
+}
+
+/// With auto-mining off, transactions stay pending until `mine_block` is called.
+#[update]
+fn set_auto_mine(auto_mine: bool) {
+    mutate_chain(|c| c.auto_mine = auto_mine);
+}
+
+/// Mines all pending transactions into a new block and returns its number.
+#[update]
+fn mine_block() -> u64 {
+    mutate_chain(|c| c.mine_block())
+}
+
+/// Sets the receipt status of a transaction, e.g. 0 to simulate a revert.
+#[update]
+fn set_receipt_status(hash: String, status: Nat) -> Result<(), String> {
+    mutate_chain(|c| {
+        let tx = c
+            .transactions
+            .iter_mut()
+            .find(|tx| tx.hash.eq_ignore_ascii_case(&hash))
+            .ok_or_else(|| format!("Unknown transaction {hash}"))?;
+        tx.status = status;
+        Ok(())
+    })
+}
+
+#[query]
+fn get_transactions() -> Vec<SentTransaction> {
+    read_chain(|c| c.transactions.clone())
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,190 @@
+//! The subset of the `evm_rpc.did` types the backend relies on. Variants the mock never
+//! returns are left out of the result types.
+#![allow(non_snake_case, clippy::large_enum_variant)]
+
+use candid::{CandidType, Deserialize, Nat};
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum EthSepoliaService {
+    Alchemy,
+    Ankr,
+    BlockPi,
+    PublicNode,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum EthMainnetService {
+    Alchemy,
+    Ankr,
+    BlockPi,
+    Cloudflare,
+    PublicNode,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpHeader {
+    pub value: String,
+    pub name: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RpcApi {
+    pub url: String,
+    pub headers: Option<Vec<HttpHeader>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RpcService {
+    EthSepolia(EthSepoliaService),
+    EthMainnet(EthMainnetService),
+    Chain(u64),
+    Provider(u64),
+    Custom(RpcApi),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RpcServices {
+    EthSepolia(Option<Vec<EthSepoliaService>>),
+    EthMainnet(Option<Vec<EthMainnetService>>),
+    Custom { chainId: u64, services: Vec<RpcApi> },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RpcConfig {
+    pub responseSizeEstimate: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum BlockTag {
+    Earliest,
+    Safe,
+    Finalized,
+    Latest,
+    Number(Nat),
+    Pending,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetTransactionCountArgs {
+    pub address: String,
+    pub block: BlockTag,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetLogsArgs {
+    pub fromBlock: Option<BlockTag>,
+    pub toBlock: Option<BlockTag>,
+    pub addresses: Vec<String>,
+    pub topics: Option<Vec<Vec<String>>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    pub transactionHash: Option<String>,
+    pub blockNumber: Option<Nat>,
+    pub data: String,
+    pub blockHash: Option<String>,
+    pub transactionIndex: Option<Nat>,
+    pub topics: Vec<String>,
+    pub address: String,
+    pub logIndex: Option<Nat>,
+    pub removed: bool,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransactionReceipt {
+    pub to: String,
+    pub status: Nat,
+    pub transactionHash: String,
+    pub blockNumber: Nat,
+    pub from: String,
+    pub logs: Vec<LogEntry>,
+    pub blockHash: String,
+    pub r#type: String,
+    pub transactionIndex: Nat,
+    pub effectiveGasPrice: Nat,
+    pub logsBloom: String,
+    pub contractAddress: Option<String>,
+    pub gasUsed: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ValidationError {
+    Custom(String),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RpcError {
+    JsonRpcError(JsonRpcError),
+    ValidationError(ValidationError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum SendRawTransactionStatus {
+    Ok(Option<String>),
+    NonceTooLow,
+    NonceTooHigh,
+    InsufficientFunds,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RequestResult {
+    Ok(String),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RequestCostResult {
+    Ok(Nat),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum GetTransactionCountResult {
+    Ok(Nat),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum MultiGetTransactionCountResult {
+    Consistent(GetTransactionCountResult),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum SendRawTransactionResult {
+    Ok(SendRawTransactionStatus),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum MultiSendRawTransactionResult {
+    Consistent(SendRawTransactionResult),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum GetTransactionReceiptResult {
+    Ok(Option<TransactionReceipt>),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum MultiGetTransactionReceiptResult {
+    Consistent(GetTransactionReceiptResult),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum GetLogsResult {
+    Ok(Vec<LogEntry>),
+    Err(RpcError),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum MultiGetLogsResult {
+    Consistent(GetLogsResult),
+}
@@ -0,0 +1,62 @@
+//! Offline stand-in for `ic_siwe_provider`. Instead of a Sign-In with Ethereum flow, addresses
+//! are linked to principals with `set_address`.
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::{query, update};
+use serde_bytes::ByteBuf;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum GetAddressResponse {
+    Ok(String),
+    Err(String),
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum GetPrincipalResponse {
+    Ok(ByteBuf),
+    Err(String),
+}
+
+thread_local! {
+    static ADDRESSES: RefCell<BTreeMap<Principal, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+fn address_of(principal: Principal) -> GetAddressResponse {
+    match ADDRESSES.with(|a| a.borrow().get(&principal).cloned()) {
+        Some(address) => GetAddressResponse::Ok(address),
+        None => GetAddressResponse::Err("Principal not found".to_string()),
+    }
+}
+
+#[query]
+fn get_address(principal: ByteBuf) -> GetAddressResponse {
+    match Principal::try_from_slice(&principal) {
+        Ok(principal) => address_of(principal),
+        Err(e) => GetAddressResponse::Err(format!("Invalid principal: {e}")),
+    }
+}
+
+#[query]
+fn get_caller_address() -> GetAddressResponse {
+    address_of(ic_cdk::caller())
+}
+
+#[query]
+fn get_principal(address: String) -> GetPrincipalResponse {
+    ADDRESSES.with(|a| {
+        a.borrow()
+            .iter()
+            .find(|(_, a)| a.eq_ignore_ascii_case(&address))
+            .map(|(principal, _)| GetPrincipalResponse::Ok(ByteBuf::from(principal.as_slice())))
+            .unwrap_or_else(|| GetPrincipalResponse::Err("Address not found".to_string()))
+    })
+}
+
+/// Links `address` to `principal`, as a successful SIWE login would.
+#[update]
+fn set_address(principal: Principal, address: String) {
+    ADDRESSES.with(|a| a.borrow_mut().insert(principal, address));
+}
+
+ic_cdk::export_candid!();